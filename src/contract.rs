@@ -1,212 +1,4160 @@
 use cosmwasm_std::{
-    to_binary, Api, BankMsg, Coin, CosmosMsg, Env, Extern, HandleResponse, HandleResult, HumanAddr,
-    InitResponse, InitResult, Querier, QueryResult, StdError, StdResult, Storage, Uint128,
+    from_binary, to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, Env, Extern, HandleResponse,
+    HandleResult, HumanAddr, InitResponse, InitResult, Querier, QueryResult, StdResult, Storage,
+    Uint128,
 };
-use cosmwasm_storage::{ReadonlySingleton, Singleton};
-use rand::{RngCore, SeedableRng};
+use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
+use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::dice::{self, DiceRule};
+use crate::error::ContractError;
+use crate::events::Event;
+use crate::permit::{self, Permission, Permit};
+use crate::snip20::{self, Token};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct StakeLimit {
+    // native or IBC (`ibc/...`) denom, or the address of one of `Config::tokens`
+    pub denom: String,
+    pub min: Uint128,
+    pub max: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Fee {
+    // share of the pot taken on settlement, in basis points (1/100 of a percent)
+    pub bps: u16,
+    pub recipient: HumanAddr,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct House {
+    // taken off the fair odds of every bet, in basis points
+    pub edge_bps: u16,
+    // most a single bet may win from the bankroll, in basis points of the bankroll
+    pub max_win_bps: u16,
+    // sides of the die bets are placed on
+    pub sides: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Jackpot {
+    // share of every player-vs-player pot added to the jackpot, in basis points
+    pub share_bps: u16,
+    // the winner of a game also wins the jackpot with a 1 in `odds` chance,
+    // e.g. 216 for the odds of three sixes on 3d6
+    pub odds: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatus {
+    Normal,
+    // only refunds, reveals, claims, withdrawals and admin messages are allowed
+    StopNewGames,
+    // only the admin can do anything, and only to change the status
+    StopAll,
+}
+
+// Who may create and join player-vs-player games, managed by the admin
+// with `HandleMsg::UpdateAccessList`
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessMode {
+    Open,
+    // only listed addresses
+    Allowlist,
+    // everyone but listed addresses
+    Denylist,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Config {
+    pub admin: HumanAddr,
+    pub status: ContractStatus,
+    // player 1 may open a game with any stake within one of these limits
+    pub stake_limits: Vec<StakeLimit>,
+    pub fee: Option<Fee>,
+    // blocks after which a game still waiting for player 2 can be expired by anyone
+    pub expiry_blocks: u64,
+    // share of the refunded stake paid to whoever expires a game, in basis points
+    pub crank_reward_bps: u16,
+    // blocks all players have to reveal their secrets once the last seat is taken
+    #[cfg(feature = "commit-reveal")]
+    pub reveal_blocks: u64,
+    // most seats a game can be created with
+    pub max_seats: u8,
+    // player-vs-house bets are disabled without it
+    pub house: Option<House>,
+    // market bets are disabled without it, paid from the same bankroll as `house`
+    pub markets: Option<Markets>,
+    pub jackpot: Option<Jackpot>,
+    // SNIP-20 tokens games can be staked in through `HandleMsg::Receive`
+    pub tokens: Vec<Token>,
+    pub access_mode: AccessMode,
+    // blocks before two players can sit at the same game again, 0 to never stop them
+    pub opponent_cooldown_blocks: u64,
+}
+
+impl Config {
+    fn token(&self, denom: &str) -> Option<&Token> {
+        self.tokens.iter().find(|token| token.address.0 == denom)
+    }
+
+    pub fn save<S: Storage>(&self, storage: &mut S) -> StdResult<()> {
+        Singleton::new(storage, b"config").save(self)
+    }
+
+    pub fn load<S: Storage>(storage: &S) -> StdResult<Config> {
+        ReadonlySingleton::new(storage, b"config").load()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GameStatus {
+    Open,
+    // commit-reveal only: all seats are taken, waiting for the players' secrets
+    Revealing,
+    Settled,
+    // commit-reveal only: the winner was rolled among the players who revealed in time
+    Forfeited,
+    // the game never filled up and the players were refunded,
+    // or with commit-reveal nobody revealed and everyone was refunded
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Seat {
+    player: HumanAddr,
+    secret: u128,
+    // commit-reveal only: cleared once the matching secret is revealed
+    commitment: Option<Binary>,
+    // the stake came from the player's balance, so winnings and refunds go back there
+    from_balance: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
-struct State {
-    player_1: Option<HumanAddr>,
-    player_1_secret: u128,
+struct Game {
+    status: GameStatus,
+
+    // number of players needed before the dice are rolled
+    seats: u8,
+    // one slot per seat, empty while free, the first one opened the game
+    // dice rules map to seats by index, so a player keeps their slot until they leave
+    players: Vec<Option<Seat>>,
+    // opened by `HandleMsg::Join`, so it goes back to matchmaking if it reopens
+    matchmaking: bool,
 
-    player_2: Option<HumanAddr>,
-    player_2_secret: u128,
+    // what each player deposited, everyone must match the first player's stake exactly
+    stake: Coin,
 
-    dice_result: u8,
+    // how the dice are rolled and who wins
+    rule: DiceRule,
+    // every die rolled when the game was settled
+    dice: Vec<u16>,
     winner: Option<HumanAddr>,
+    // jackpot paid to the winner on top of the pot, zero unless they hit it
+    jackpot: Uint128,
+
+    created_at_height: u64,
+    created_at_time: u64,
+
+    // commit-reveal only: first block at which an unrevealed secret forfeits the game
+    reveal_deadline: u64,
+    // commit-reveal only: the contract's prng seed and the block height when the last seat
+    // was taken, the dice are rolled from these and the secrets alone
+    //
+    // contract state is readable there, so the last player to reveal would otherwise know
+    // the roll for every block up to the deadline and could reveal in one they win
+    sealed_entropy: Option<([u8; 32], u64)>,
+
+    // block height at which the game was settled, 0 while still open
+    settled_at: u64,
 }
 
-impl State {
-    pub fn save<S: Storage>(&self, storage: &mut S) -> StdResult<()> {
-        Singleton::new(storage, b"state").save(self)
+impl Game {
+    pub fn save<S: Storage>(&self, storage: &mut S, game_id: u64) -> StdResult<()> {
+        Bucket::new(b"games", storage).save(&game_id.to_be_bytes(), self)
+    }
+
+    pub fn load<S: Storage>(storage: &S, game_id: u64) -> StdResult<Game> {
+        ReadonlyBucket::new(b"games", storage)
+            .may_load(&game_id.to_be_bytes())?
+            .ok_or_else(|| ContractError::GameNotFound { game_id }.into())
+    }
+
+    pub fn remove<S: Storage>(storage: &mut S, game_id: u64) {
+        Bucket::<S, Game>::new(b"games", storage).remove(&game_id.to_be_bytes())
+    }
+
+    // An open game can't be joined anymore once it's past its expiry
+    pub fn is_expired(&self, config: &Config, height: u64) -> bool {
+        self.status == GameStatus::Open
+            && height >= self.created_at_height.saturating_add(config.expiry_blocks)
+    }
+
+    // A game waiting for secrets can be forfeited once it's past its reveal deadline
+    pub fn is_reveal_expired(&self, height: u64) -> bool {
+        self.status == GameStatus::Revealing && height >= self.reveal_deadline
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players.iter().all(Option::is_some)
+    }
+
+    // Taken seats along with their index
+    pub fn seated(&self) -> impl Iterator<Item = (usize, &Seat)> {
+        self.players
+            .iter()
+            .enumerate()
+            .filter_map(|(index, seat)| seat.as_ref().map(|seat| (index, seat)))
+    }
+
+    pub fn taken_seats(&self) -> usize {
+        self.seated().count()
+    }
+
+    pub fn seat_of(&self, player: &HumanAddr) -> Option<usize> {
+        self.seated()
+            .find(|(_, seat)| &seat.player == player)
+            .map(|(index, _)| index)
+    }
+
+    pub fn seat(&self, game_id: u64, index: usize) -> StdResult<&Seat> {
+        self.players
+            .get(index)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| {
+                ContractError::EmptySeat {
+                    game_id,
+                    seat: index,
+                }
+                .into()
+            })
     }
 
-    pub fn load<S: Storage>(storage: &S) -> StdResult<State> {
-        ReadonlySingleton::new(storage, b"state").load()
+    // Everything staked in this game
+    pub fn pot(&self) -> StdResult<Uint128> {
+        self.stake
+            .amount
+            .u128()
+            .checked_mul(self.taken_seats() as u128)
+            .map(Uint128)
+            .ok_or_else(|| ContractError::Overflow {}.into())
     }
 }
 
-//////////////////////////////////////////////////////////////////////
-//////////////////////////////// Init ////////////////////////////////
-//////////////////////////////////////////////////////////////////////
+// What a player commits to in commit-reveal mode: sha256(secret as 16 big-endian bytes || address)
+//
+// Binding the address in stops other players from copying someone's commitment
+pub fn commitment(secret: u128, player: &HumanAddr) -> Binary {
+    let mut preimage = secret.to_be_bytes().to_vec();
+    preimage.extend(player.as_str().as_bytes());
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+    Binary(Sha256::digest(&preimage).to_vec())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct InitMsg {}
+pub enum HouseBet {
+    // wins if the roll is lower than `target`
+    RollUnder { target: u16 },
+    // wins if the roll is higher than `target`
+    RollOver { target: u16 },
+    // wins if the roll is exactly `number`
+    Exact { number: u16 },
+}
 
-pub fn init<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    _env: Env,
-    _msg: InitMsg,
-) -> InitResult {
-    let state = State {
-        player_1: None,
-        player_1_secret: 0,
+impl HouseBet {
+    // How many faces of a die with `sides` faces win this bet
+    pub fn winning_faces(&self, sides: u16) -> StdResult<u16> {
+        let faces = match *self {
+            HouseBet::RollUnder { target } => target.min(sides + 1).saturating_sub(1),
+            HouseBet::RollOver { target } => sides.saturating_sub(target),
+            HouseBet::Exact { number } if number >= 1 && number <= sides => 1,
+            HouseBet::Exact { .. } => 0,
+        };
 
-        player_2: None,
-        player_2_secret: 0,
+        if faces == 0 || faces >= sides {
+            return Err(ContractError::InvalidBet {
+                reason: format!("Bet must win on some but not all faces of a d{}.", sides),
+            }
+            .into());
+        }
 
-        dice_result: 0,
-        winner: None,
-    };
+        Ok(faces)
+    }
 
-    state.save(&mut deps.storage)?;
+    pub fn wins(&self, roll: u16) -> bool {
+        match *self {
+            HouseBet::RollUnder { target } => roll < target,
+            HouseBet::RollOver { target } => roll > target,
+            HouseBet::Exact { number } => roll == number,
+        }
+    }
+}
 
-    Ok(InitResponse::default())
+#[derive(Serialize, Deserialize, Clone)]
+struct HouseGame {
+    player: HumanAddr,
+    bet: HouseBet,
+    stake: Coin,
+    roll: u16,
+    // everything paid back to the player, including their stake, zero on a loss
+    payout: Uint128,
+    settled_at: u64,
 }
 
-//////////////////////////////////////////////////////////////////////
-/////////////////////////////// Handle ///////////////////////////////
-//////////////////////////////////////////////////////////////////////
+impl HouseGame {
+    #[cfg(not(feature = "commit-reveal"))]
+    pub fn save<S: Storage>(&self, storage: &mut S, game_id: u64) -> StdResult<()> {
+        Bucket::new(b"house_games", storage).save(&game_id.to_be_bytes(), self)
+    }
+
+    pub fn load<S: Storage>(storage: &S, game_id: u64) -> StdResult<HouseGame> {
+        ReadonlyBucket::new(b"house_games", storage)
+            .may_load(&game_id.to_be_bytes())?
+            .ok_or_else(|| ContractError::GameNotFound { game_id }.into())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketBet {
+    // wins if the total of all dice is higher than `total`
+    Over { total: u32 },
+    // wins if the total of all dice is lower than `total`
+    Under { total: u32 },
+    // wins if the total of all dice is exactly `total`
+    Exactly { total: u32 },
+    // wins if every die shows the same face
+    Doubles {},
+}
+
+impl MarketBet {
+    pub fn wins(&self, dice: &[u16]) -> bool {
+        let total = dice::total(dice);
+
+        match *self {
+            MarketBet::Over { total: over } => total > over,
+            MarketBet::Under { total: under } => total < under,
+            MarketBet::Exactly { total: exactly } => total == exactly,
+            MarketBet::Doubles {} => dice.windows(2).all(|pair| pair[0] == pair[1]),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum HandleMsg {
-    Join { secret: u128 },
-    Leave {},
+pub struct Payout {
+    pub bet: MarketBet,
+    // everything paid back on a win, including the stake, in basis points of the stake
+    pub multiplier_bps: u32,
+    // most a single bet on this market may win from the bankroll, in basis points of the bankroll
+    pub max_win_bps: u16,
 }
 
-pub fn handle<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
-    msg: HandleMsg,
-) -> HandleResult {
-    match msg {
-        HandleMsg::Join { secret } => {
-            // player 1 joins, sends a secret and deposits 1 SCRT to the contract
-            // player 1's secret is stored privately
-            //
-            // player 2 joins, sends a secret and deposits 1 SCRT to the contract
-            // player 2's secret is stored privately
-            //
-            // once player 2 joins, we can derive a shared secret that no one knows
-            // then we can roll the dice and choose a winner
-            // dice roll 1-3: player 1 wins / dice roll 4-6: player 2 wins
-            //
-            // the winner then gets 2 SCRT
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Markets {
+    pub dice_count: u8,
+    pub sides: u16,
+    // the only bets that can be placed, e.g. `over 7` at 24000 bps for 2d6
+    pub payouts: Vec<Payout>,
+}
+
+impl Markets {
+    fn validate(&self) -> StdResult<()> {
+        if self.dice_count == 0 || self.dice_count > dice::MAX_DICE {
+            return Err(ContractError::InvalidConfig {
+                reason: format!("Markets must roll between 1 and {} dice.", dice::MAX_DICE),
+            }
+            .into());
+        }
+
+        if self.sides < 2 || self.sides > dice::MAX_SIDES {
+            return Err(ContractError::InvalidConfig {
+                reason: format!(
+                    "Market dice must have between 2 and {} sides.",
+                    dice::MAX_SIDES
+                ),
+            }
+            .into());
+        }
+
+        let min_total = self.dice_count as u32;
+        let max_total = self.dice_count as u32 * self.sides as u32;
 
-            if env.message.sent_funds.len() != 1
-                || env.message.sent_funds[0].amount
-                    != Uint128(1_000_000 /* 1mn uscrt = 1 SCRT */)
-                || env.message.sent_funds[0].denom != String::from("uscrt")
+        for (i, payout) in self.payouts.iter().enumerate() {
+            let possible = match payout.bet {
+                MarketBet::Over { total } => total >= min_total && total < max_total,
+                MarketBet::Under { total } => total > min_total && total <= max_total,
+                MarketBet::Exactly { total } => total >= min_total && total <= max_total,
+                MarketBet::Doubles {} => self.dice_count >= 2,
+            };
+
+            if !possible {
+                return Err(ContractError::InvalidConfig {
+                    reason: format!("Market {} can never be won or never be lost.", i),
+                }
+                .into());
+            }
+
+            if payout.multiplier_bps <= 10_000 || payout.max_win_bps > 10_000 {
+                return Err(ContractError::InvalidConfig { reason: format!(
+                    "Market {} must pay more than its stake and win at most 10000 bps of the bankroll.",
+                    i
+                ) }.into());
+            }
+
+            if self.payouts[..i]
+                .iter()
+                .any(|other| other.bet == payout.bet)
             {
-                return Err(StdError::generic_err(
-                    "Must deposit 1 SCRT to enter the game.",
-                ));
+                return Err(ContractError::InvalidConfig {
+                    reason: format!("Market {} is listed twice.", i),
+                }
+                .into());
             }
+        }
 
-            let mut state = State::load(&deps.storage)?;
+        Ok(())
+    }
 
-            if state.player_1.is_none() {
-                state.player_1 = Some(env.message.sender);
-                state.player_1_secret = secret;
+    #[cfg(not(feature = "commit-reveal"))]
+    fn payout(&self, bet: &MarketBet) -> StdResult<&Payout> {
+        self.payouts
+            .iter()
+            .find(|payout| payout.bet == *bet)
+            .ok_or_else(|| ContractError::NoMarket {}.into())
+    }
+}
 
-                state.save(&mut deps.storage)?;
+#[derive(Serialize, Deserialize, Clone)]
+struct MarketGame {
+    player: HumanAddr,
+    bet: MarketBet,
+    stake: Coin,
+    dice: Vec<u16>,
+    multiplier_bps: u32,
+    // everything paid back to the player, including their stake, zero on a loss
+    payout: Uint128,
+    settled_at: u64,
+}
 
-                Ok(HandleResponse::default())
-            } else if state.player_2.is_none() {
-                state.player_2 = Some(env.message.sender);
-                state.player_2_secret = secret;
+impl MarketGame {
+    #[cfg(not(feature = "commit-reveal"))]
+    pub fn save<S: Storage>(&self, storage: &mut S, game_id: u64) -> StdResult<()> {
+        Bucket::new(b"market_games", storage).save(&game_id.to_be_bytes(), self)
+    }
 
-                let mut combined_secret: Vec<u8> = state.player_1_secret.to_be_bytes().to_vec();
-                combined_secret.extend(&state.player_2_secret.to_be_bytes());
+    pub fn load<S: Storage>(storage: &S, game_id: u64) -> StdResult<MarketGame> {
+        ReadonlyBucket::new(b"market_games", storage)
+            .may_load(&game_id.to_be_bytes())?
+            .ok_or_else(|| ContractError::GameNotFound { game_id }.into())
+    }
+}
 
-                let random_seed: [u8; 32] = Sha256::digest(&combined_secret).into();
-                let mut rng = ChaChaRng::from_seed(random_seed);
+// What the house holds to pay out winning bets, per denom
+//
+// Kept apart from the players' stakes, which the contract holds as well
+fn load_bankroll<S: Storage>(storage: &S, denom: &str) -> StdResult<Uint128> {
+    Ok(ReadonlyBucket::new(b"bankroll", storage)
+        .may_load(denom.as_bytes())?
+        .unwrap_or_else(Uint128::zero))
+}
 
-                state.dice_result = ((rng.next_u32() % 6) + 1) as u8; // a number between 1 and 6
+fn save_bankroll<S: Storage>(storage: &mut S, denom: &str, amount: Uint128) -> StdResult<()> {
+    Bucket::new(b"bankroll", storage).save(denom.as_bytes(), &amount)
+}
 
-                if state.dice_result >= 1 && state.dice_result <= 3 {
-                    state.winner = state.player_1.clone();
-                } else {
-                    state.winner = state.player_2.clone();
-                }
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct JackpotPool {
+    amount: Uint128,
+    last_won_game: Option<u64>,
+}
 
-                state.save(&mut deps.storage)?;
+// The jackpot of each denom, kept apart from the games so it outlives them
+fn load_jackpot<S: Storage>(storage: &S, denom: &str) -> StdResult<JackpotPool> {
+    Ok(ReadonlyBucket::new(b"jackpot", storage)
+        .may_load(denom.as_bytes())?
+        .unwrap_or_default())
+}
 
-                Ok(HandleResponse {
-                    messages: vec![CosmosMsg::Bank(BankMsg::Send {
-                        from_address: env.contract.address,
-                        to_address: state.winner.unwrap(),
-                        amount: vec![Coin::new(2_000_000, "uscrt")], // 1mn uscrt = 1 SCRT
-                    })],
-                    log: vec![],
-                    data: None,
-                })
-            } else {
-                Err(StdError::generic_err("Game is full."))
-            }
+fn save_jackpot<S: Storage>(storage: &mut S, denom: &str, pool: &JackpotPool) -> StdResult<()> {
+    Bucket::new(b"jackpot", storage).save(denom.as_bytes(), pool)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GameKind {
+    // player-vs-player, see `QueryMsg::GetResult`
+    Dice,
+    // see `QueryMsg::GetHouseGame`
+    House,
+    // see `QueryMsg::GetMarketBet`
+    Market,
+}
+
+// Every game a player took part in, oldest first
+fn load_player_games<S: Storage>(
+    storage: &S,
+    player: &HumanAddr,
+) -> StdResult<Vec<(u64, GameKind)>> {
+    Ok(ReadonlyBucket::new(b"player_games", storage)
+        .may_load(player.0.as_bytes())?
+        .unwrap_or_default())
+}
+
+fn add_player_game<S: Storage>(
+    storage: &mut S,
+    player: &HumanAddr,
+    game_id: u64,
+    kind: GameKind,
+) -> StdResult<()> {
+    let mut games = load_player_games(storage, player)?;
+    games.push((game_id, kind));
+    Bucket::new(b"player_games", storage).save(player.0.as_bytes(), &games)
+}
+
+fn remove_player_game<S: Storage>(
+    storage: &mut S,
+    player: &HumanAddr,
+    game_id: u64,
+) -> StdResult<()> {
+    let mut games = load_player_games(storage, player)?;
+    games.retain(|&(id, _)| id != game_id);
+    Bucket::new(b"player_games", storage).save(player.0.as_bytes(), &games)
+}
+
+// Only the sha256 of each viewing key is stored
+fn save_viewing_key<S: Storage>(storage: &mut S, player: &HumanAddr, key: &str) -> StdResult<()> {
+    let hash: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+    Bucket::new(b"viewing_keys", storage).save(player.0.as_bytes(), &hash)
+}
+
+fn check_viewing_key<S: Storage>(storage: &S, player: &HumanAddr, key: &str) -> StdResult<()> {
+    let stored: Option<[u8; 32]> =
+        ReadonlyBucket::new(b"viewing_keys", storage).may_load(player.0.as_bytes())?;
+    let hash: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+
+    // compare every byte even without a stored key, so timing doesn't tell whether one was set
+    let matches = stored
+        .unwrap_or([0u8; 32])
+        .iter()
+        .zip(hash.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0;
+
+    if stored.is_none() || !matches {
+        return Err(ContractError::WrongViewingKey {}.into());
+    }
+
+    Ok(())
+}
+
+// What each player holds in the contract, per denom
+//
+// Credited by `Deposit` and by winnings and refunds of games played from it
+fn load_balance<S: Storage>(storage: &S, player: &HumanAddr, denom: &str) -> StdResult<Uint128> {
+    Ok(
+        ReadonlyBucket::multilevel(&[b"balances", player.0.as_bytes()], storage)
+            .may_load(denom.as_bytes())?
+            .unwrap_or_else(Uint128::zero),
+    )
+}
+
+fn save_balance<S: Storage>(
+    storage: &mut S,
+    player: &HumanAddr,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    Bucket::multilevel(&[b"balances", player.0.as_bytes()], storage).save(denom.as_bytes(), &amount)
+}
+
+fn credit<S: Storage>(storage: &mut S, player: &HumanAddr, amount: &Coin) -> StdResult<()> {
+    let balance = load_balance(storage, player, &amount.denom)?
+        .u128()
+        .checked_add(amount.amount.u128())
+        .ok_or(ContractError::Overflow {})?;
+    save_balance(storage, player, &amount.denom, Uint128(balance))
+}
+
+fn debit<S: Storage>(storage: &mut S, player: &HumanAddr, amount: &Coin) -> StdResult<()> {
+    let balance = load_balance(storage, player, &amount.denom)?;
+    if amount.amount > balance {
+        return Err(ContractError::InsufficientBalance {
+            available: Coin {
+                denom: amount.denom.clone(),
+                amount: balance,
+            },
         }
-        HandleMsg::Leave {} => {
-            // if player 2 isn't in yet, player 1 can leave and get their money back
+        .into());
+    }
+    save_balance(storage, player, &amount.denom, (balance - amount.amount)?)
+}
 
-            let mut state = State::load(&deps.storage)?;
+// Winnings of settled games waiting for `Claim` or `ClaimAll`, by game id
+fn load_unclaimed<S: Storage>(storage: &S, player: &HumanAddr) -> StdResult<Vec<(u64, Coin)>> {
+    Ok(ReadonlyBucket::new(b"unclaimed", storage)
+        .may_load(player.0.as_bytes())?
+        .unwrap_or_default())
+}
 
-            if state.player_1.as_ref() != Some(&env.message.sender) {
-                return Err(StdError::generic_err("You are not a player."));
-            }
+fn save_unclaimed<S: Storage>(
+    storage: &mut S,
+    player: &HumanAddr,
+    unclaimed: &[(u64, Coin)],
+) -> StdResult<()> {
+    if unclaimed.is_empty() {
+        Bucket::<S, Vec<(u64, Coin)>>::new(b"unclaimed", storage).remove(player.0.as_bytes());
+        return Ok(());
+    }
+    Bucket::new(b"unclaimed", storage).save(player.0.as_bytes(), &unclaimed.to_vec())
+}
+
+// Addresses on the allowlist or denylist, whichever `Config::access_mode` uses
+fn is_listed<S: Storage>(storage: &S, player: &HumanAddr) -> StdResult<bool> {
+    Ok(ReadonlyBucket::new(b"access_list", storage)
+        .may_load(player.0.as_bytes())?
+        .unwrap_or(false))
+}
+
+fn set_listed<S: Storage>(storage: &mut S, player: &HumanAddr, listed: bool) -> StdResult<()> {
+    let mut bucket = Bucket::new(b"access_list", storage);
+    if listed {
+        bucket.save(player.0.as_bytes(), &true)
+    } else {
+        bucket.remove(player.0.as_bytes());
+        Ok(())
+    }
+}
+
+fn assert_may_play<S: Storage>(storage: &S, config: &Config, player: &HumanAddr) -> StdResult<()> {
+    let allowed = match config.access_mode {
+        AccessMode::Open => true,
+        AccessMode::Allowlist => is_listed(storage, player)?,
+        AccessMode::Denylist => !is_listed(storage, player)?,
+    };
+
+    if !allowed {
+        return Err(ContractError::NotAllowedToPlay {}.into());
+    }
+
+    Ok(())
+}
+
+// Block height at which two players last sat at the same game, stored both ways round
+fn load_last_met<S: Storage>(
+    storage: &S,
+    player: &HumanAddr,
+    opponent: &HumanAddr,
+) -> StdResult<Option<u64>> {
+    ReadonlyBucket::multilevel(&[b"last_met", player.0.as_bytes()], storage)
+        .may_load(opponent.0.as_bytes())
+}
+
+fn save_last_met<S: Storage>(
+    storage: &mut S,
+    player: &HumanAddr,
+    opponent: &HumanAddr,
+    height: u64,
+) -> StdResult<()> {
+    Bucket::multilevel(&[b"last_met", player.0.as_bytes()], storage)
+        .save(opponent.0.as_bytes(), &height)?;
+    Bucket::multilevel(&[b"last_met", opponent.0.as_bytes()], storage)
+        .save(player.0.as_bytes(), &height)
+}
+
+// A player already at `game` that `player` met less than `opponent_cooldown_blocks` ago,
+// and the height at which they may meet again
+//
+// slows down one player matching their own second wallet to farm the jackpot
+fn recent_opponent<S: Storage>(
+    storage: &S,
+    config: &Config,
+    height: u64,
+    player: &HumanAddr,
+    game: &Game,
+) -> StdResult<Option<(HumanAddr, u64)>> {
+    if config.opponent_cooldown_blocks == 0 {
+        return Ok(None);
+    }
 
-            if state.winner.is_some() {
-                return Err(StdError::generic_err(format!(
-                    "Game is already over. Winner is {}.",
-                    state.winner.unwrap()
-                )));
+    for (_, seat) in game.seated() {
+        if let Some(last_met) = load_last_met(storage, player, &seat.player)? {
+            let until = last_met.saturating_add(config.opponent_cooldown_blocks);
+            if height < until {
+                return Ok(Some((seat.player.clone(), until)));
             }
+        }
+    }
 
-            state.player_1 = None;
-            state.player_1_secret = 0;
+    Ok(None)
+}
 
-            state.save(&mut deps.storage)?;
+// The fee recipient can also be the winner of the same game, so both end up in one entry
+fn add_unclaimed<S: Storage>(
+    storage: &mut S,
+    player: &HumanAddr,
+    game_id: u64,
+    amount: Coin,
+) -> StdResult<()> {
+    let mut unclaimed = load_unclaimed(storage, player)?;
 
-            Ok(HandleResponse {
-                messages: vec![CosmosMsg::Bank(BankMsg::Send {
-                    from_address: env.contract.address,
-                    to_address: env.message.sender,
-                    amount: vec![Coin::new(1_000_000, "uscrt")], // 1mn uscrt = 1 SCRT
-                })],
-                log: vec![],
-                data: None,
-            })
+    match unclaimed.iter_mut().find(|(id, _)| *id == game_id) {
+        Some((_, coin)) => {
+            coin.amount = Uint128(
+                coin.amount
+                    .u128()
+                    .checked_add(amount.amount.u128())
+                    .ok_or(ContractError::Overflow {})?,
+            )
         }
+        None => unclaimed.push((game_id, amount)),
     }
+
+    save_unclaimed(storage, player, &unclaimed)
 }
 
-///////////////////////////////////////////////////////////////////////
-//////////////////////////////// Query ////////////////////////////////
-///////////////////////////////////////////////////////////////////////
+// Game ids are handed out sequentially, starting from 0
+fn next_game_id<S: Storage>(storage: &mut S) -> StdResult<u64> {
+    let game_id: u64 = ReadonlySingleton::new(storage, b"game_count").load()?;
+    Singleton::new(storage, b"game_count").save(&(game_id + 1))?;
+    Ok(game_id)
+}
 
-// These are getters, we only return what's public
+// The two-seat rounds `HandleMsg::Join` fills, oldest first, for each stake
+// so every table size and denom gets matched with its own kind
+//
+// there can be more than one, as a player may have to skip a round they can't join yet
+fn load_open_rounds<S: Storage>(storage: &S, stake: &Coin) -> StdResult<Vec<u64>> {
+    Ok(
+        ReadonlyBucket::multilevel(&[b"open_rounds", stake.denom.as_bytes()], storage)
+            .may_load(&stake.amount.u128().to_be_bytes())?
+            .unwrap_or_default(),
+    )
+}
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum QueryMsg {
-    GetResult {},
+fn save_open_rounds<S: Storage>(storage: &mut S, stake: &Coin, game_ids: &[u64]) -> StdResult<()> {
+    let mut bucket = Bucket::multilevel(&[b"open_rounds", stake.denom.as_bytes()], storage);
+    if game_ids.is_empty() {
+        bucket.remove(&stake.amount.u128().to_be_bytes());
+        Ok(())
+    } else {
+        bucket.save(&stake.amount.u128().to_be_bytes(), &game_ids.to_vec())
+    }
+}
+
+fn remove_open_round<S: Storage>(storage: &mut S, stake: &Coin, game_id: u64) -> StdResult<()> {
+    let mut game_ids = load_open_rounds(storage, stake)?;
+    game_ids.retain(|&id| id != game_id);
+    save_open_rounds(storage, stake, &game_ids)
+}
+
+// The most recently settled game, returned by `QueryMsg::GetResult` when no id is given
+fn load_last_settled<S: Storage>(storage: &S) -> StdResult<Option<u64>> {
+    ReadonlySingleton::new(storage, b"last_settled").load()
+}
+
+fn save_last_settled<S: Storage>(storage: &mut S, game_id: u64) -> StdResult<()> {
+    Singleton::new(storage, b"last_settled").save(&Some(game_id))
+}
+
+// Contract-held entropy, never returned by any query and ratcheted after every roll
+fn load_prng_seed<S: Storage>(storage: &S) -> StdResult<[u8; 32]> {
+    ReadonlySingleton::new(storage, b"prng_seed").load()
+}
+
+fn save_prng_seed<S: Storage>(storage: &mut S, seed: &[u8; 32]) -> StdResult<()> {
+    Singleton::new(storage, b"prng_seed").save(seed)
+}
+
+// Derives the dice RNG seed for a game and the contract's next prng seed
+//
+// Every input is domain separated and length prefixed, so no two different games can hash the same
+fn roll_seed(
+    prng_seed: &[u8; 32],
+    height: u64,
+    contract: &HumanAddr,
+    game_id: u64,
+    secrets: &[u128],
+) -> ([u8; 32], [u8; 32]) {
+    let mut hasher = Sha256::new();
+    hasher.update(b"secret-dice/roll/v1");
+    hasher.update(prng_seed);
+    hasher.update(game_id.to_be_bytes());
+    hasher.update(height.to_be_bytes());
+    hasher.update((contract.len() as u64).to_be_bytes());
+    hasher.update(contract.as_str().as_bytes());
+    hasher.update((secrets.len() as u64).to_be_bytes());
+    for secret in secrets {
+        hasher.update(secret.to_be_bytes());
+    }
+    let random_seed: [u8; 32] = hasher.finalize().into();
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"secret-dice/ratchet/v1");
+    hasher.update(prng_seed);
+    hasher.update(random_seed);
+    let next_prng_seed: [u8; 32] = hasher.finalize().into();
+
+    (random_seed, next_prng_seed)
 }
+
+//////////////////////////////////////////////////////////////////////
+//////////////////////////////// Init ////////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-struct Result {
-    winner: HumanAddr,
-    dice_roll: u8,
+pub struct InitMsg {
+    // defaults to the instantiator
+    pub admin: Option<HumanAddr>,
+    // initial contract entropy, mixed into every dice roll
+    pub prng_seed: Binary,
+    pub stake_limits: Vec<StakeLimit>,
+    pub fee: Option<Fee>,
+    // defaults to DEFAULT_EXPIRY_BLOCKS
+    pub expiry_blocks: Option<u64>,
+    // defaults to no reward
+    pub crank_reward_bps: Option<u16>,
+    // defaults to DEFAULT_REVEAL_BLOCKS
+    #[cfg(feature = "commit-reveal")]
+    pub reveal_blocks: Option<u64>,
+    // defaults to DEFAULT_MAX_SEATS
+    pub max_seats: Option<u8>,
+    // enables player-vs-house bets
+    pub house: Option<House>,
+    // enables market bets
+    pub markets: Option<Markets>,
+    // enables the jackpot
+    pub jackpot: Option<Jackpot>,
+    // the contract registers itself with each of them, defaults to none
+    pub tokens: Option<Vec<Token>>,
+    // defaults to open, the lists start out empty
+    pub access_mode: Option<AccessMode>,
+    // defaults to no cooldown
+    pub opponent_cooldown_blocks: Option<u64>,
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
-    match msg {
-        QueryMsg::GetResult {} => {
-            let state = State::load(&deps.storage)?;
+// ~1 day with 6 second blocks
+pub const DEFAULT_EXPIRY_BLOCKS: u64 = 14_400;
 
-            if state.winner.is_none() {
-                return Err(StdError::generic_err("Still waiting for players."));
-            }
+// ~10 minutes with 6 second blocks
+#[cfg(feature = "commit-reveal")]
+pub const DEFAULT_REVEAL_BLOCKS: u64 = 100;
+
+pub const DEFAULT_MAX_SEATS: u8 = 6;
 
-            return Ok(to_binary(&Result {
-                winner: state.winner.unwrap(),
-                dice_roll: state.dice_result,
-            })?);
+// Without a custom rule each seat wins on its own run of this many faces of a single die,
+// so a 2 seat game is the classic d6 where 1-3 wins for the first player and 4-6 for the second
+pub const FACES_PER_SEAT: u8 = 3;
+
+// Keeps the classic die used for the roll small
+pub const MAX_SEATS: u8 = u8::MAX / FACES_PER_SEAT;
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: InitMsg,
+) -> InitResult {
+    let admin = msg.admin.unwrap_or(env.message.sender);
+    deps.api.canonical_address(&admin)?;
+
+    if msg.stake_limits.is_empty() {
+        return Err(ContractError::InvalidConfig {
+            reason: "Must accept at least one denom.".to_string(),
+        }
+        .into());
+    }
+
+    for (i, limit) in msg.stake_limits.iter().enumerate() {
+        if limit.denom.is_empty() {
+            return Err(ContractError::InvalidConfig {
+                reason: "Stake denom must not be empty.".to_string(),
+            }
+            .into());
+        }
+        if limit.min.is_zero() || limit.min > limit.max {
+            return Err(ContractError::InvalidConfig {
+                reason: format!("Invalid stake limits for {}.", limit.denom),
+            }
+            .into());
+        }
+        if msg.stake_limits[..i].iter().any(|l| l.denom == limit.denom) {
+            return Err(ContractError::InvalidConfig {
+                reason: format!("Duplicate stake limits for {}.", limit.denom),
+            }
+            .into());
         }
     }
+
+    if let Some(fee) = &msg.fee {
+        if fee.bps > 10_000 {
+            return Err(ContractError::InvalidConfig {
+                reason: "Fee must not be more than 10000 bps.".to_string(),
+            }
+            .into());
+        }
+        // fail early on a malformed fee recipient
+        deps.api.canonical_address(&fee.recipient)?;
+    }
+
+    if let Some(jackpot) = &msg.jackpot {
+        let fee_bps = msg.fee.as_ref().map_or(0, |fee| fee.bps);
+        if fee_bps as u32 + jackpot.share_bps as u32 > 10_000 {
+            return Err(ContractError::InvalidConfig {
+                reason: "Fee and jackpot share must not be more than 10000 bps together."
+                    .to_string(),
+            }
+            .into());
+        }
+        if jackpot.odds < 2 {
+            return Err(ContractError::InvalidConfig {
+                reason: "Jackpot odds must be at least 2.".to_string(),
+            }
+            .into());
+        }
+    }
+
+    let expiry_blocks = msg.expiry_blocks.unwrap_or(DEFAULT_EXPIRY_BLOCKS);
+    if expiry_blocks == 0 {
+        return Err(ContractError::InvalidConfig {
+            reason: "Expiry must be at least one block.".to_string(),
+        }
+        .into());
+    }
+
+    let crank_reward_bps = msg.crank_reward_bps.unwrap_or(0);
+    if crank_reward_bps > 10_000 {
+        return Err(ContractError::InvalidConfig {
+            reason: "Crank reward must not be more than 10000 bps.".to_string(),
+        }
+        .into());
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    let reveal_blocks = msg.reveal_blocks.unwrap_or(DEFAULT_REVEAL_BLOCKS);
+    #[cfg(feature = "commit-reveal")]
+    if reveal_blocks == 0 {
+        return Err(ContractError::InvalidConfig {
+            reason: "Reveal period must be at least one block.".to_string(),
+        }
+        .into());
+    }
+
+    let max_seats = msg.max_seats.unwrap_or(DEFAULT_MAX_SEATS);
+    if !(2..=MAX_SEATS).contains(&max_seats) {
+        return Err(ContractError::InvalidConfig {
+            reason: format!("Max seats must be between 2 and {}.", MAX_SEATS),
+        }
+        .into());
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    if msg.house.is_some() || msg.markets.is_some() {
+        return Err(ContractError::InvalidConfig {
+            reason: "House games are not available with commit-reveal.".to_string(),
+        }
+        .into());
+    }
+
+    if let Some(house) = &msg.house {
+        if house.edge_bps >= 10_000 || house.max_win_bps > 10_000 {
+            return Err(ContractError::InvalidConfig {
+                reason: "House edge and max win must be less than 10000 bps.".to_string(),
+            }
+            .into());
+        }
+        if house.sides < 2 || house.sides > dice::MAX_SIDES {
+            return Err(ContractError::InvalidConfig {
+                reason: format!(
+                    "House dice must have between 2 and {} sides.",
+                    dice::MAX_SIDES
+                ),
+            }
+            .into());
+        }
+    }
+
+    let tokens = msg.tokens.unwrap_or_default();
+
+    if let Some(markets) = &msg.markets {
+        markets.validate()?;
+    }
+
+    Config {
+        admin,
+        status: ContractStatus::Normal,
+        stake_limits: msg.stake_limits,
+        fee: msg.fee,
+        expiry_blocks,
+        crank_reward_bps,
+        #[cfg(feature = "commit-reveal")]
+        reveal_blocks,
+        max_seats,
+        house: msg.house,
+        markets: msg.markets,
+        jackpot: msg.jackpot,
+        tokens: tokens.clone(),
+        access_mode: msg.access_mode.unwrap_or(AccessMode::Open),
+        opponent_cooldown_blocks: msg.opponent_cooldown_blocks.unwrap_or(0),
+    }
+    .save(&mut deps.storage)?;
+
+    Singleton::new(&mut deps.storage, b"game_count").save(&0u64)?;
+    Singleton::new(&mut deps.storage, b"last_settled").save(&None::<u64>)?;
+
+    let prng_seed: [u8; 32] = Sha256::digest(msg.prng_seed.as_slice()).into();
+    save_prng_seed(&mut deps.storage, &prng_seed)?;
+
+    // lets the contract's own token balances be checked, never stored or returned by any query
+    let viewing_key = Binary(
+        Sha256::new()
+            .chain(b"secret-dice/token-viewing-key/v1")
+            .chain(prng_seed)
+            .finalize()
+            .to_vec(),
+    )
+    .to_base64();
+
+    // queries don't know which contract they run in, but permits must be checked against it
+    Singleton::new(&mut deps.storage, b"contract_address").save(&env.contract.address)?;
+
+    let mut messages = vec![];
+    for token in &tokens {
+        messages.push(snip20::register_receive_msg(
+            token,
+            env.contract_code_hash.clone(),
+        )?);
+        messages.push(snip20::set_viewing_key_msg(token, viewing_key.clone())?);
+    }
+
+    Ok(InitResponse {
+        messages,
+        log: vec![],
+    })
+}
+
+//////////////////////////////////////////////////////////////////////
+/////////////////////////////// Handle ///////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    // `Join` seats the sender in a two-seat round waiting for the same stake, or opens one
+    //
+    // `stake` is taken from the sender's balance instead of the funds sent along,
+    // winnings and refunds then go back to the balance
+    #[cfg(not(feature = "commit-reveal"))]
+    Join {
+        secret: u128,
+        stake: Option<Coin>,
+    },
+    // `seats` defaults to 2, `rule` to the classic single die (see FACES_PER_SEAT)
+    #[cfg(not(feature = "commit-reveal"))]
+    CreateGame {
+        seats: Option<u8>,
+        rule: Option<DiceRule>,
+        secret: u128,
+        stake: Option<Coin>,
+    },
+    #[cfg(not(feature = "commit-reveal"))]
+    JoinGame {
+        game_id: u64,
+        secret: u128,
+        stake: Option<Coin>,
+    },
+
+    // with commit-reveal players first commit to `commitment(secret, address)`
+    // and only send the secret itself with `Reveal` once all seats are taken
+    #[cfg(feature = "commit-reveal")]
+    Join {
+        hash: Binary,
+        stake: Option<Coin>,
+    },
+    #[cfg(feature = "commit-reveal")]
+    CreateGame {
+        seats: Option<u8>,
+        rule: Option<DiceRule>,
+        hash: Binary,
+        stake: Option<Coin>,
+    },
+    #[cfg(feature = "commit-reveal")]
+    JoinGame {
+        game_id: u64,
+        hash: Binary,
+        stake: Option<Coin>,
+    },
+    #[cfg(feature = "commit-reveal")]
+    Reveal {
+        game_id: u64,
+        secret: u128,
+    },
+
+    // refunds the sender's stake and frees their seat, until the game settles
+    // with commit-reveal the last player who hasn't revealed has to reveal or forfeit
+    Leave {
+        game_id: u64,
+    },
+    // anyone can refund the players of a game that expired before it filled up,
+    // or with commit-reveal settle a game whose reveal deadline passed
+    ExpireGame {
+        game_id: u64,
+    },
+
+    // bet against the house bankroll, settled right away
+    //
+    // not available with commit-reveal, as the house's seed would be readable there
+    #[cfg(not(feature = "commit-reveal"))]
+    PlayHouse {
+        bet: HouseBet,
+        secret: u128,
+    },
+    // bet on one of the markets listed in the config, settled right away
+    #[cfg(not(feature = "commit-reveal"))]
+    PlaceBet {
+        bet: MarketBet,
+        secret: u128,
+    },
+
+    // adds the funds sent along to the sender's balance, to stake later without sending funds
+    Deposit {},
+    Withdraw {
+        amount: Coin,
+    },
+
+    // pays out the sender's winnings of a settled game, or of all their games at once
+    Claim {
+        game_id: u64,
+    },
+    ClaimAll {},
+
+    // called by a token contract in `Config::tokens` when `from` sends it `amount`,
+    // `msg` holds what to do with them
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+    },
+
+    // the new key is returned in `data`, derived from `entropy` and the contract's own entropy
+    CreateViewingKey {
+        entropy: String,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    // the sender's permits with this name are no longer accepted
+    RevokePermit {
+        name: String,
+    },
+
+    // admin only
+    FundBankroll {},
+    SetAccessMode {
+        mode: AccessMode,
+    },
+    UpdateAccessList {
+        add: Vec<HumanAddr>,
+        remove: Vec<HumanAddr>,
+    },
+    WithdrawBankroll {
+        amount: Coin,
+    },
+    SetContractStatus {
+        level: ContractStatus,
+    },
+}
+
+// What can be done with tokens sent through `HandleMsg::Receive`, same as the `HandleMsg` variants
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    #[cfg(not(feature = "commit-reveal"))]
+    Join {
+        secret: u128,
+    },
+    #[cfg(not(feature = "commit-reveal"))]
+    CreateGame {
+        seats: Option<u8>,
+        rule: Option<DiceRule>,
+        secret: u128,
+    },
+    #[cfg(not(feature = "commit-reveal"))]
+    JoinGame {
+        game_id: u64,
+        secret: u128,
+    },
+
+    #[cfg(feature = "commit-reveal")]
+    Join {
+        hash: Binary,
+    },
+    #[cfg(feature = "commit-reveal")]
+    CreateGame {
+        seats: Option<u8>,
+        rule: Option<DiceRule>,
+        hash: Binary,
+    },
+    #[cfg(feature = "commit-reveal")]
+    JoinGame {
+        game_id: u64,
+        hash: Binary,
+    },
+
+    Deposit {},
+}
+
+// Sends `amount` to `recipient`, with a SNIP-20 transfer if it's staked in a token
+fn payment(env: &Env, config: &Config, recipient: HumanAddr, amount: Coin) -> StdResult<CosmosMsg> {
+    match config.token(&amount.denom) {
+        Some(token) => snip20::transfer_msg(token, recipient, amount.amount),
+        None => Ok(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: recipient,
+            amount: vec![amount],
+        })),
+    }
+}
+
+// Pays `amount` back to whoever sits in `seat`, into their balance if they staked from it
+fn pay_seat<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    config: &Config,
+    seat: &Seat,
+    amount: Coin,
+) -> StdResult<Option<CosmosMsg>> {
+    if seat.from_balance {
+        credit(storage, &seat.player, &amount)?;
+        return Ok(None);
+    }
+
+    payment(env, config, seat.player.clone(), amount).map(Some)
+}
+
+// With `stake` set, takes it from the sender's balance and hands it on as if it was sent along
+fn take_stake<S: Storage>(
+    storage: &mut S,
+    mut env: Env,
+    stake: Option<Coin>,
+) -> StdResult<(Env, bool)> {
+    let stake = match stake {
+        Some(stake) => stake,
+        None => return Ok((env, false)),
+    };
+
+    if !env.message.sent_funds.is_empty() {
+        return Err(ContractError::FundsWithBalanceStake {}.into());
+    }
+
+    debit(storage, &env.message.sender, &stake)?;
+    env.message.sent_funds = vec![stake];
+
+    Ok((env, true))
+}
+
+fn deposit<S: Storage>(storage: &mut S, env: &Env, config: &Config) -> HandleResult {
+    if env.message.sent_funds.is_empty() {
+        return Err(ContractError::NoFunds {}.into());
+    }
+
+    for coin in &env.message.sent_funds {
+        if !config
+            .stake_limits
+            .iter()
+            .any(|limit| limit.denom == coin.denom)
+        {
+            return Err(ContractError::DenomNotAccepted {
+                denom: coin.denom.clone(),
+            }
+            .into());
+        }
+
+        credit(storage, &env.message.sender, coin)?;
+    }
+
+    Ok(HandleResponse::default())
+}
+
+// Returned in `data` by the handle messages that act on a game, and by `CreateViewingKey`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    Created {
+        game_id: u64,
+    },
+    // the game is settled in the same transaction when the last seat is taken without
+    // commit-reveal, and `Settled` is returned instead
+    Joined {
+        game_id: u64,
+        seat: usize,
+    },
+    Revealed {
+        game_id: u64,
+        seat: usize,
+    },
+    Settled {
+        game_id: u64,
+        winner: HumanAddr,
+        // every die rolled, grouped by seat for `HighestTotal`
+        dice_roll: Vec<u16>,
+        // what the winner won, jackpot included, to be claimed with `Claim`
+        // unless they staked from their balance
+        payout: Coin,
+    },
+    Left {
+        game_id: u64,
+        refunded: Coin,
+    },
+    Expired {
+        game_id: u64,
+        // to every player
+        refunded: Coin,
+        // to the sender, for all players together
+        reward: Coin,
+    },
+    HouseBet {
+        game_id: u64,
+        roll: u16,
+        // zero on a loss
+        payout: Coin,
+    },
+    MarketBet {
+        game_id: u64,
+        dice_roll: Vec<u16>,
+        // zero on a loss
+        payout: Coin,
+    },
+    // one coin per denom, for all games claimed
+    Claimed {
+        game_ids: Vec<u64>,
+        payout: Vec<Coin>,
+    },
+    CreateViewingKey {
+        key: String,
+    },
+}
+
+// Returns the single coin sent along with the message
+fn single_deposit(env: &Env) -> StdResult<Coin> {
+    if env.message.sent_funds.len() != 1 {
+        return Err(ContractError::NotOneCoin {
+            got: env.message.sent_funds.len(),
+        }
+        .into());
+    }
+
+    Ok(env.message.sent_funds[0].clone())
+}
+
+// Returns the deposited coin if it's within the stake limits of its denom
+fn assert_stake(env: &Env, config: &Config) -> StdResult<Coin> {
+    let stake = single_deposit(env)?;
+
+    let limit = config
+        .stake_limits
+        .iter()
+        .find(|limit| limit.denom == stake.denom)
+        .ok_or_else(|| ContractError::DenomNotAccepted {
+            denom: stake.denom.clone(),
+        })?;
+
+    if stake.amount < limit.min || stake.amount > limit.max {
+        return Err(ContractError::StakeOutOfRange {
+            min: limit.min,
+            max: limit.max,
+            denom: limit.denom.clone(),
+        }
+        .into());
+    }
+
+    Ok(stake)
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> HandleResult {
+    let config = Config::load(&deps.storage)?;
+
+    match (config.status, &msg) {
+        (_, HandleMsg::SetContractStatus { .. }) => {}
+        (ContractStatus::Normal, _) => {}
+        (ContractStatus::StopNewGames, HandleMsg::Leave { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::ExpireGame { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::CreateViewingKey { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::SetViewingKey { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::RevokePermit { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::FundBankroll { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::WithdrawBankroll { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::SetAccessMode { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::UpdateAccessList { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::Withdraw { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::Claim { .. })
+        | (ContractStatus::StopNewGames, HandleMsg::ClaimAll { .. }) => {}
+        #[cfg(feature = "commit-reveal")]
+        (ContractStatus::StopNewGames, HandleMsg::Reveal { .. }) => {}
+        (ContractStatus::StopNewGames, _) => {
+            return Err(ContractError::NotAcceptingGames {}.into());
+        }
+        (ContractStatus::StopAll, _) => {
+            return Err(ContractError::Paused {}.into());
+        }
+    }
+
+    // token stakes use the token address as their denom, which must only ever come from `Receive`
+    if let Some(coin) = env
+        .message
+        .sent_funds
+        .iter()
+        .find(|coin| config.token(&coin.denom).is_some())
+    {
+        return Err(ContractError::TokenOnlyThroughReceive {
+            denom: coin.denom.clone(),
+        }
+        .into());
+    }
+
+    match msg {
+        #[cfg(not(feature = "commit-reveal"))]
+        HandleMsg::Join { secret, stake } => {
+            let (env, from_balance) = take_stake(&mut deps.storage, env, stake)?;
+            join(deps, env, &config, secret, None, from_balance)
+        }
+        #[cfg(not(feature = "commit-reveal"))]
+        HandleMsg::CreateGame {
+            seats,
+            rule,
+            secret,
+            stake,
+        } => {
+            let (env, from_balance) = take_stake(&mut deps.storage, env, stake)?;
+            let game_id = create_game(
+                deps,
+                env,
+                seats.unwrap_or(2),
+                rule,
+                secret,
+                None,
+                from_balance,
+            )?;
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: Event::Create { game_id }.log(),
+                data: Some(to_binary(&HandleAnswer::Created { game_id })?),
+            })
+        }
+        #[cfg(not(feature = "commit-reveal"))]
+        HandleMsg::JoinGame {
+            game_id,
+            secret,
+            stake,
+        } => {
+            let (env, from_balance) = take_stake(&mut deps.storage, env, stake)?;
+            join_game(deps, env, game_id, secret, None, from_balance)
+        }
+        #[cfg(feature = "commit-reveal")]
+        HandleMsg::Join { hash, stake } => {
+            let (env, from_balance) = take_stake(&mut deps.storage, env, stake)?;
+            join(deps, env, &config, 0, Some(hash), from_balance)
+        }
+        #[cfg(feature = "commit-reveal")]
+        HandleMsg::CreateGame {
+            seats,
+            rule,
+            hash,
+            stake,
+        } => {
+            let (env, from_balance) = take_stake(&mut deps.storage, env, stake)?;
+            let game_id = create_game(
+                deps,
+                env,
+                seats.unwrap_or(2),
+                rule,
+                0,
+                Some(hash),
+                from_balance,
+            )?;
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: Event::Create { game_id }.log(),
+                data: Some(to_binary(&HandleAnswer::Created { game_id })?),
+            })
+        }
+        #[cfg(feature = "commit-reveal")]
+        HandleMsg::JoinGame {
+            game_id,
+            hash,
+            stake,
+        } => {
+            let (env, from_balance) = take_stake(&mut deps.storage, env, stake)?;
+            join_game(deps, env, game_id, 0, Some(hash), from_balance)
+        }
+        #[cfg(feature = "commit-reveal")]
+        HandleMsg::Reveal { game_id, secret } => {
+            // each player reveals the secret they committed to
+            // once all secrets are in we roll the dice as usual
+
+            let mut game = Game::load(&deps.storage, game_id)?;
+
+            if game.status != GameStatus::Revealing {
+                return Err(ContractError::NotRevealing { game_id }.into());
+            }
+
+            if game.is_reveal_expired(env.block.height) {
+                return Err(ContractError::RevealDeadlinePassed { game_id }.into());
+            }
+
+            let seat_index = game
+                .seat_of(&env.message.sender)
+                .ok_or(ContractError::NotAPlayer {})?;
+            let seat = game.players[seat_index]
+                .as_mut()
+                .ok_or(ContractError::EmptySeat {
+                    game_id,
+                    seat: seat_index,
+                })?;
+
+            match &seat.commitment {
+                None => return Err(ContractError::AlreadyRevealed {}.into()),
+                Some(hash) if *hash != commitment(secret, &env.message.sender) => {
+                    return Err(ContractError::CommitmentMismatch {}.into());
+                }
+                Some(_) => {}
+            }
+
+            seat.secret = secret;
+            seat.commitment = None;
+
+            if game.seated().all(|(_, seat)| seat.commitment.is_none()) {
+                let eligible = (0..game.players.len()).collect::<Vec<_>>();
+                let mut res = settle_game(deps, env, &config, game_id, game, &eligible)?;
+
+                let reveal = Event::Reveal {
+                    game_id,
+                    seat: seat_index,
+                };
+                res.log = [reveal.log(), res.log].concat();
+                return Ok(res);
+            }
+
+            game.save(&mut deps.storage, game_id)?;
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: Event::Reveal {
+                    game_id,
+                    seat: seat_index,
+                }
+                .log(),
+                data: Some(to_binary(&HandleAnswer::Revealed {
+                    game_id,
+                    seat: seat_index,
+                })?),
+            })
+        }
+        HandleMsg::Receive {
+            from, amount, msg, ..
+        } => {
+            // a token contract tells us `from` sent us `amount` of its tokens
+            // the rest of the contract handles them as if `from` had sent a coin
+            // of the token's denom along with the inner message
+
+            let token = config.token(&env.message.sender.0).ok_or_else(|| {
+                ContractError::TokenNotAccepted {
+                    token: env.message.sender.clone(),
+                }
+            })?;
+
+            let msg: ReceiveMsg = match msg {
+                Some(msg) => from_binary(&msg)?,
+                None => return Err(ContractError::MissingReceiveMsg {}.into()),
+            };
+
+            let mut env = env;
+            env.message.sent_funds = vec![Coin {
+                denom: token.address.0.clone(),
+                amount,
+            }];
+            env.message.sender = from;
+
+            match msg {
+                #[cfg(not(feature = "commit-reveal"))]
+                ReceiveMsg::Join { secret } => join(deps, env, &config, secret, None, false),
+                #[cfg(not(feature = "commit-reveal"))]
+                ReceiveMsg::CreateGame {
+                    seats,
+                    rule,
+                    secret,
+                } => {
+                    let game_id =
+                        create_game(deps, env, seats.unwrap_or(2), rule, secret, None, false)?;
+
+                    Ok(HandleResponse {
+                        messages: vec![],
+                        log: Event::Create { game_id }.log(),
+                        data: Some(to_binary(&HandleAnswer::Created { game_id })?),
+                    })
+                }
+                #[cfg(not(feature = "commit-reveal"))]
+                ReceiveMsg::JoinGame { game_id, secret } => {
+                    join_game(deps, env, game_id, secret, None, false)
+                }
+                #[cfg(feature = "commit-reveal")]
+                ReceiveMsg::Join { hash } => join(deps, env, &config, 0, Some(hash), false),
+                #[cfg(feature = "commit-reveal")]
+                ReceiveMsg::CreateGame { seats, rule, hash } => {
+                    let game_id =
+                        create_game(deps, env, seats.unwrap_or(2), rule, 0, Some(hash), false)?;
+
+                    Ok(HandleResponse {
+                        messages: vec![],
+                        log: Event::Create { game_id }.log(),
+                        data: Some(to_binary(&HandleAnswer::Created { game_id })?),
+                    })
+                }
+                #[cfg(feature = "commit-reveal")]
+                ReceiveMsg::JoinGame { game_id, hash } => {
+                    join_game(deps, env, game_id, 0, Some(hash), false)
+                }
+                ReceiveMsg::Deposit {} => deposit(&mut deps.storage, &env, &config),
+            }
+        }
+        HandleMsg::Leave { game_id } => {
+            // any player can leave a game that hasn't settled yet and get their own stake back
+            // their seat is freed for someone else, and once the last player leaves
+            // the game is discarded
+            //
+            // with commit-reveal a game waiting for secrets goes back to waiting for players
+            // and its expiry starts over, but the last player yet to reveal can't leave,
+            // or they could back out of every roll they don't like instead of forfeiting
+
+            let mut game = Game::load(&deps.storage, game_id)?;
+
+            let seat = game
+                .seat_of(&env.message.sender)
+                .ok_or(ContractError::NotAPlayer {})?;
+
+            if let Some(winner) = game.winner {
+                return Err(ContractError::GameOver { winner }.into());
+            }
+
+            match game.status {
+                GameStatus::Open => {}
+                GameStatus::Revealing if !game.is_reveal_expired(env.block.height) => {}
+                GameStatus::Revealing => {
+                    return Err(ContractError::RevealDeadlinePassed { game_id }.into());
+                }
+                GameStatus::Expired => {
+                    return Err(ContractError::AlreadyRefunded { game_id }.into());
+                }
+                _ => return Err(ContractError::GameStarted { game_id }.into()),
+            }
+
+            if game.status == GameStatus::Revealing {
+                let unrevealed = game
+                    .seated()
+                    .filter(|(_, seat)| seat.commitment.is_some())
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>();
+
+                if unrevealed == [seat] {
+                    return Err(ContractError::LastToReveal { game_id }.into());
+                }
+            }
+
+            let leaving = game.players[seat]
+                .take()
+                .ok_or(ContractError::EmptySeat { game_id, seat })?;
+            remove_player_game(&mut deps.storage, &leaving.player, game_id)?;
+
+            if game.taken_seats() == 0 {
+                Game::remove(&mut deps.storage, game_id);
+
+                remove_open_round(&mut deps.storage, &game.stake, game_id)?;
+            } else {
+                if game.status == GameStatus::Revealing {
+                    game.status = GameStatus::Open;
+                    game.reveal_deadline = 0;
+                    game.sealed_entropy = None;
+                    game.created_at_height = env.block.height;
+                    game.created_at_time = env.block.time;
+
+                    // a round `Join` was filling goes back to matchmaking
+                    if game.matchmaking {
+                        let mut open_rounds = load_open_rounds(&deps.storage, &game.stake)?;
+                        open_rounds.push(game_id);
+                        save_open_rounds(&mut deps.storage, &game.stake, &open_rounds)?;
+                    }
+                }
+
+                game.save(&mut deps.storage, game_id)?;
+            }
+
+            let refunded = game.stake.clone();
+            let refund = pay_seat(&mut deps.storage, &env, &config, &leaving, refunded.clone())?;
+
+            Ok(HandleResponse {
+                messages: refund.into_iter().collect(),
+                log: Event::Leave { game_id, seat }.log(),
+                data: Some(to_binary(&HandleAnswer::Left { game_id, refunded })?),
+            })
+        }
+        HandleMsg::ExpireGame { game_id } => {
+            let game = Game::load(&deps.storage, game_id)?;
+
+            if game.is_reveal_expired(env.block.height) {
+                return forfeit_game(deps, env, &config, game_id, game);
+            }
+
+            // if the game never filled up, anyone can refund its players after the expiry
+            // the caller gets a share of each stake as a reward, if configured
+
+            let mut game = game;
+
+            // same answers as `Leave` for a game that's already over
+            if let Some(winner) = game.winner {
+                return Err(ContractError::GameOver { winner }.into());
+            }
+            if game.status == GameStatus::Expired {
+                return Err(ContractError::AlreadyRefunded { game_id }.into());
+            }
+
+            if !game.is_expired(&config, env.block.height) {
+                return Err(ContractError::NotExpiredYet {
+                    game_id,
+                    expires_at: game.created_at_height.saturating_add(config.expiry_blocks),
+                }
+                .into());
+            }
+
+            game.status = GameStatus::Expired;
+            game.settled_at = env.block.height;
+            game.save(&mut deps.storage, game_id)?;
+
+            remove_open_round(&mut deps.storage, &game.stake, game_id)?;
+
+            let reward = game
+                .stake
+                .amount
+                .multiply_ratio(config.crank_reward_bps, 10_000u128);
+
+            let refunded = Coin {
+                denom: game.stake.denom.clone(),
+                amount: (game.stake.amount - reward)?,
+            };
+            let reward = Coin {
+                denom: game.stake.denom.clone(),
+                amount: Uint128(
+                    reward
+                        .u128()
+                        .checked_mul(game.taken_seats() as u128)
+                        .ok_or(ContractError::Overflow {})?,
+                ),
+            };
+
+            let mut messages = vec![];
+            for (_, seat) in game.seated() {
+                messages.extend(pay_seat(
+                    &mut deps.storage,
+                    &env,
+                    &config,
+                    seat,
+                    refunded.clone(),
+                )?);
+            }
+
+            if !reward.amount.is_zero() {
+                messages.push(payment(
+                    &env,
+                    &config,
+                    env.message.sender.clone(),
+                    reward.clone(),
+                )?);
+            }
+
+            Ok(HandleResponse {
+                messages,
+                log: Event::Expire { game_id }.log(),
+                data: Some(to_binary(&HandleAnswer::Expired {
+                    game_id,
+                    refunded,
+                    reward,
+                })?),
+            })
+        }
+        HandleMsg::Deposit {} => deposit(&mut deps.storage, &env, &config),
+        HandleMsg::Withdraw { amount } => {
+            debit(&mut deps.storage, &env.message.sender, &amount)?;
+
+            Ok(HandleResponse {
+                messages: vec![payment(&env, &config, env.message.sender.clone(), amount)?],
+                log: vec![],
+                data: None,
+            })
+        }
+        HandleMsg::Claim { game_id } => {
+            let mut unclaimed = load_unclaimed(&deps.storage, &env.message.sender)?;
+
+            let index = unclaimed
+                .iter()
+                .position(|(id, _)| *id == game_id)
+                .ok_or(ContractError::NothingToClaim {})?;
+            let claimed = unclaimed.remove(index);
+
+            save_unclaimed(&mut deps.storage, &env.message.sender, &unclaimed)?;
+
+            claim(&env, &config, vec![claimed])
+        }
+        HandleMsg::ClaimAll {} => {
+            let unclaimed = load_unclaimed(&deps.storage, &env.message.sender)?;
+
+            if unclaimed.is_empty() {
+                return Err(ContractError::NothingToClaim {}.into());
+            }
+
+            save_unclaimed(&mut deps.storage, &env.message.sender, &[])?;
+
+            claim(&env, &config, unclaimed)
+        }
+        #[cfg(not(feature = "commit-reveal"))]
+        HandleMsg::PlayHouse { bet, secret } => play_house(deps, env, &config, bet, secret),
+        #[cfg(not(feature = "commit-reveal"))]
+        HandleMsg::PlaceBet { bet, secret } => place_bet(deps, env, &config, bet, secret),
+        HandleMsg::FundBankroll {} => {
+            if env.message.sender != config.admin {
+                return Err(ContractError::Unauthorized {}.into());
+            }
+
+            if env.message.sent_funds.is_empty() {
+                return Err(ContractError::NoFunds {}.into());
+            }
+
+            for coin in &env.message.sent_funds {
+                if !config
+                    .stake_limits
+                    .iter()
+                    .any(|limit| limit.denom == coin.denom)
+                {
+                    return Err(ContractError::DenomNotAccepted {
+                        denom: coin.denom.clone(),
+                    }
+                    .into());
+                }
+
+                let bankroll = load_bankroll(&deps.storage, &coin.denom)?;
+                let bankroll = bankroll
+                    .u128()
+                    .checked_add(coin.amount.u128())
+                    .ok_or(ContractError::Overflow {})?;
+                save_bankroll(&mut deps.storage, &coin.denom, Uint128(bankroll))?;
+            }
+
+            Ok(HandleResponse::default())
+        }
+        HandleMsg::WithdrawBankroll { amount } => {
+            if env.message.sender != config.admin {
+                return Err(ContractError::Unauthorized {}.into());
+            }
+
+            let bankroll = load_bankroll(&deps.storage, &amount.denom)?;
+            if amount.amount > bankroll {
+                return Err(ContractError::InsufficientBankroll {
+                    available: Coin {
+                        denom: amount.denom,
+                        amount: bankroll,
+                    },
+                }
+                .into());
+            }
+
+            save_bankroll(
+                &mut deps.storage,
+                &amount.denom,
+                (bankroll - amount.amount)?,
+            )?;
+
+            Ok(HandleResponse {
+                messages: vec![CosmosMsg::Bank(BankMsg::Send {
+                    from_address: env.contract.address,
+                    to_address: env.message.sender,
+                    amount: vec![amount],
+                })],
+                log: vec![],
+                data: None,
+            })
+        }
+        HandleMsg::CreateViewingKey { entropy } => {
+            let prng_seed = load_prng_seed(&deps.storage)?;
+
+            let hash = Sha256::new()
+                .chain(b"secret-dice/viewing-key/v1")
+                .chain(prng_seed)
+                .chain(env.block.height.to_be_bytes())
+                .chain(env.block.time.to_be_bytes())
+                .chain(env.message.sender.0.as_bytes())
+                .chain(entropy.as_bytes())
+                .finalize();
+            let key = format!("api_key_{}", Binary(hash.to_vec()).to_base64());
+
+            save_viewing_key(&mut deps.storage, &env.message.sender, &key)?;
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: vec![],
+                data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+            })
+        }
+        HandleMsg::SetViewingKey { key, .. } => {
+            save_viewing_key(&mut deps.storage, &env.message.sender, &key)?;
+
+            Ok(HandleResponse::default())
+        }
+        HandleMsg::RevokePermit { name } => {
+            permit::revoke(&mut deps.storage, &env.message.sender, &name)?;
+
+            Ok(HandleResponse::default())
+        }
+        HandleMsg::SetAccessMode { mode } => {
+            let mut config = config;
+
+            if env.message.sender != config.admin {
+                return Err(ContractError::Unauthorized {}.into());
+            }
+
+            config.access_mode = mode;
+            config.save(&mut deps.storage)?;
+
+            Ok(HandleResponse::default())
+        }
+        HandleMsg::UpdateAccessList { add, remove } => {
+            if env.message.sender != config.admin {
+                return Err(ContractError::Unauthorized {}.into());
+            }
+
+            for address in &add {
+                set_listed(&mut deps.storage, address, true)?;
+            }
+            for address in &remove {
+                set_listed(&mut deps.storage, address, false)?;
+            }
+
+            Ok(HandleResponse::default())
+        }
+        HandleMsg::SetContractStatus { level } => {
+            let mut config = config;
+
+            if env.message.sender != config.admin {
+                return Err(ContractError::Unauthorized {}.into());
+            }
+
+            config.status = level;
+            config.save(&mut deps.storage)?;
+
+            Ok(HandleResponse::default())
+        }
+    }
+}
+
+// Sends the sender what they won in `claimed`, with a single payment per denom
+fn claim(env: &Env, config: &Config, claimed: Vec<(u64, Coin)>) -> HandleResult {
+    let mut payout: Vec<Coin> = vec![];
+    let mut log = vec![];
+
+    for (game_id, coin) in &claimed {
+        log.extend(
+            Event::Claim {
+                game_id: *game_id,
+                payout: coin,
+            }
+            .log(),
+        );
+
+        match payout.iter_mut().find(|total| total.denom == coin.denom) {
+            Some(total) => {
+                total.amount = Uint128(
+                    total
+                        .amount
+                        .u128()
+                        .checked_add(coin.amount.u128())
+                        .ok_or(ContractError::Overflow {})?,
+                )
+            }
+            None => payout.push(coin.clone()),
+        }
+    }
+
+    let messages = payout
+        .iter()
+        .map(|coin| payment(env, config, env.message.sender.clone(), coin.clone()))
+        .collect::<StdResult<Vec<CosmosMsg>>>()?;
+
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: Some(to_binary(&HandleAnswer::Claimed {
+            game_ids: claimed.into_iter().map(|(game_id, _)| game_id).collect(),
+            payout,
+        })?),
+    })
+}
+
+fn join<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: &Config,
+    secret: u128,
+    commitment: Option<Binary>,
+    from_balance: bool,
+) -> HandleResult {
+    // matchmaking: join the oldest open two-seat round for the same stake,
+    // otherwise open a new round
+    // once a round is settled the next `Join` automatically opens a fresh one
+    //
+    // the sender's own rounds are skipped, and so are rounds whose player they met
+    // too recently, those keep waiting for someone else
+    // expired rounds are dropped from matchmaking and left for `ExpireGame`
+
+    let stake = assert_stake(&env, config)?;
+
+    let mut open_rounds = load_open_rounds(&deps.storage, &stake)?;
+    let mut expired = vec![];
+    let mut open_round = None;
+
+    for &game_id in &open_rounds {
+        let game = Game::load(&deps.storage, game_id)?;
+
+        if game.is_expired(config, env.block.height) {
+            expired.push(game_id);
+            continue;
+        }
+
+        let skipped = game.seat_of(&env.message.sender).is_some()
+            || recent_opponent(
+                &deps.storage,
+                config,
+                env.block.height,
+                &env.message.sender,
+                &game,
+            )?
+            .is_some();
+
+        if !skipped {
+            open_round = Some(game_id);
+            break;
+        }
+    }
+
+    if !expired.is_empty() {
+        open_rounds.retain(|game_id| !expired.contains(game_id));
+        save_open_rounds(&mut deps.storage, &stake, &open_rounds)?;
+    }
+
+    match open_round {
+        Some(game_id) => join_game(deps, env, game_id, secret, commitment, from_balance),
+        None => {
+            let game_id = create_game(deps, env, 2, None, secret, commitment, from_balance)?;
+
+            let mut game = Game::load(&deps.storage, game_id)?;
+            game.matchmaking = true;
+            game.save(&mut deps.storage, game_id)?;
+
+            open_rounds.push(game_id);
+            save_open_rounds(&mut deps.storage, &stake, &open_rounds)?;
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: Event::Create { game_id }.log(),
+                data: Some(to_binary(&HandleAnswer::Created { game_id })?),
+            })
+        }
+    }
+}
+
+// With commit-reveal `secret` is unused until `Reveal` and `commitment` is set
+fn create_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    seats: u8,
+    rule: Option<DiceRule>,
+    secret: u128,
+    commitment: Option<Binary>,
+    from_balance: bool,
+) -> StdResult<u64> {
+    // the first player opens a new game with a number of seats and dice rule, sends a secret
+    // and deposits a stake of their choice
+    // their secret is stored privately
+    //
+    // the new game's id is returned in the log so the other players can join it
+
+    let config = Config::load(&deps.storage)?;
+    let stake = assert_stake(&env, &config)?;
+
+    assert_may_play(&deps.storage, &config, &env.message.sender)?;
+
+    if seats < 2 || seats > config.max_seats {
+        return Err(ContractError::InvalidSeats {
+            max: config.max_seats,
+        }
+        .into());
+    }
+
+    let rule = rule.unwrap_or_else(|| DiceRule::classic(seats, FACES_PER_SEAT));
+    rule.validate(seats)?;
+
+    assert_commitment(&commitment)?;
+
+    let game_id = next_game_id(&mut deps.storage)?;
+    add_player_game(
+        &mut deps.storage,
+        &env.message.sender,
+        game_id,
+        GameKind::Dice,
+    )?;
+
+    let mut players = vec![None; seats as usize];
+    players[0] = Some(Seat {
+        player: env.message.sender,
+        secret,
+        commitment,
+        from_balance,
+    });
+
+    let game = Game {
+        status: GameStatus::Open,
+
+        seats,
+        players,
+        matchmaking: false,
+
+        stake,
+
+        rule,
+        dice: vec![],
+        winner: None,
+        jackpot: Uint128::zero(),
+
+        created_at_height: env.block.height,
+        created_at_time: env.block.time,
+
+        reveal_deadline: 0,
+        sealed_entropy: None,
+
+        settled_at: 0,
+    };
+
+    game.save(&mut deps.storage, game_id)?;
+
+    Ok(game_id)
+}
+
+fn assert_commitment(commitment: &Option<Binary>) -> StdResult<()> {
+    match commitment {
+        Some(hash) if hash.len() != 32 => Err(ContractError::InvalidCommitment {}.into()),
+        _ => Ok(()),
+    }
+}
+
+fn join_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    game_id: u64,
+    secret: u128,
+    commitment: Option<Binary>,
+    from_balance: bool,
+) -> HandleResult {
+    // another player joins, sends a secret and deposits the same stake to the contract
+    // their secret is stored privately
+    //
+    // once the last seat is taken the game is settled, or with commit-reveal
+    // all players now have to reveal their secrets first
+
+    let config = Config::load(&deps.storage)?;
+    let stake = single_deposit(&env)?;
+
+    let mut game = Game::load(&deps.storage, game_id)?;
+
+    if game.is_full() {
+        return Err(ContractError::GameFull { game_id }.into());
+    }
+
+    if game.status == GameStatus::Expired || game.is_expired(&config, env.block.height) {
+        return Err(ContractError::GameExpired { game_id }.into());
+    }
+
+    // whoever holds every seat picks every secret, and so the roll
+    if game.seat_of(&env.message.sender).is_some() {
+        return Err(ContractError::AlreadySeated { game_id }.into());
+    }
+
+    assert_may_play(&deps.storage, &config, &env.message.sender)?;
+
+    if let Some((opponent, until)) = recent_opponent(
+        &deps.storage,
+        &config,
+        env.block.height,
+        &env.message.sender,
+        &game,
+    )? {
+        return Err(ContractError::RecentOpponent { opponent, until }.into());
+    }
+
+    if stake != game.stake {
+        return Err(ContractError::WrongDeposit {
+            expected: game.stake,
+            got: stake,
+        }
+        .into());
+    }
+
+    assert_commitment(&commitment)?;
+
+    let is_commitment = commitment.is_some();
+
+    add_player_game(
+        &mut deps.storage,
+        &env.message.sender,
+        game_id,
+        GameKind::Dice,
+    )?;
+
+    if config.opponent_cooldown_blocks > 0 {
+        for (_, seat) in game.seated() {
+            save_last_met(
+                &mut deps.storage,
+                &env.message.sender,
+                &seat.player,
+                env.block.height,
+            )?;
+        }
+    }
+
+    // the first free seat, a player who left may have freed one before the last
+    let index = game
+        .players
+        .iter()
+        .position(Option::is_none)
+        .ok_or(ContractError::GameFull { game_id })?;
+    game.players[index] = Some(Seat {
+        player: env.message.sender.clone(),
+        secret,
+        commitment,
+        from_balance,
+    });
+
+    if !game.is_full() {
+        game.save(&mut deps.storage, game_id)?;
+
+        return Ok(HandleResponse {
+            messages: vec![],
+            log: Event::Join {
+                game_id,
+                seat: index,
+            }
+            .log(),
+            data: Some(to_binary(&HandleAnswer::Joined {
+                game_id,
+                seat: index,
+            })?),
+        });
+    }
+
+    remove_open_round(&mut deps.storage, &game.stake, game_id)?;
+
+    if !is_commitment {
+        let eligible = (0..game.players.len()).collect::<Vec<_>>();
+        let mut res = settle_game(deps, env, &config, game_id, game, &eligible)?;
+
+        let join = Event::Join {
+            game_id,
+            seat: index,
+        };
+        res.log = [join.log(), res.log].concat();
+        return Ok(res);
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    {
+        game.reveal_deadline = env.block.height.saturating_add(config.reveal_blocks);
+
+        // the contract's seed moves on right away, this game keeps the one it was sealed with
+        let prng_seed = load_prng_seed(&deps.storage)?;
+        let (_, next_prng_seed) = roll_seed(
+            &prng_seed,
+            env.block.height,
+            &env.contract.address,
+            game_id,
+            &[],
+        );
+        save_prng_seed(&mut deps.storage, &next_prng_seed)?;
+        game.sealed_entropy = Some((prng_seed, env.block.height));
+    }
+
+    game.status = GameStatus::Revealing;
+    game.save(&mut deps.storage, game_id)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: Event::Join {
+            game_id,
+            seat: index,
+        }
+        .log(),
+        data: Some(to_binary(&HandleAnswer::Joined {
+            game_id,
+            seat: index,
+        })?),
+    })
+}
+
+#[cfg(not(feature = "commit-reveal"))]
+fn play_house<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: &Config,
+    bet: HouseBet,
+    secret: u128,
+) -> HandleResult {
+    // the player bets against the house and the die is rolled right away
+    // the player's secret and the contract's own entropy seed the roll
+    //
+    // a winning bet pays the fair odds minus the house edge, e.g. on a d6 "roll under 4"
+    // wins on 3 faces and pays 6/3 = 2x the stake before the edge
+    // the house can only lose up to a share of its bankroll on a single bet
+
+    let house = config
+        .house
+        .as_ref()
+        .ok_or(ContractError::HouseDisabled {})?;
+
+    let stake = assert_stake(&env, config)?;
+    assert_may_play(&deps.storage, config, &env.message.sender)?;
+
+    let winning_faces = bet.winning_faces(house.sides)?;
+
+    let payout = stake
+        .amount
+        .u128()
+        .checked_mul(house.sides as u128 * (10_000 - house.edge_bps) as u128)
+        .map(|amount| Uint128(amount / (winning_faces as u128 * 10_000)))
+        .ok_or(ContractError::Overflow {})?;
+
+    if payout <= stake.amount {
+        return Err(ContractError::InvalidBet {
+            reason: "Bet can't win more than its stake after the house edge.".to_string(),
+        }
+        .into());
+    }
+
+    let house_risk = (payout - stake.amount)?;
+    let bankroll = load_bankroll(&deps.storage, &stake.denom)?;
+    let max_win = bankroll
+        .u128()
+        .checked_mul(house.max_win_bps as u128)
+        .map(|amount| Uint128(amount / 10_000))
+        .ok_or(ContractError::Overflow {})?;
+
+    if house_risk > max_win {
+        return Err(ContractError::ExceedsMaxWin {
+            potential_win: Coin {
+                denom: stake.denom.clone(),
+                amount: house_risk,
+            },
+            max_win: Coin {
+                denom: stake.denom,
+                amount: max_win,
+            },
+        }
+        .into());
+    }
+
+    let game_id = next_game_id(&mut deps.storage)?;
+
+    let prng_seed = load_prng_seed(&deps.storage)?;
+    let (random_seed, next_prng_seed) = roll_seed(
+        &prng_seed,
+        env.block.height,
+        &env.contract.address,
+        game_id,
+        &[secret],
+    );
+    save_prng_seed(&mut deps.storage, &next_prng_seed)?;
+
+    let mut rng = ChaChaRng::from_seed(random_seed);
+    let roll = dice::roll(&mut rng, house.sides)?;
+
+    let mut messages = vec![];
+    let payout = if bet.wins(roll) {
+        save_bankroll(&mut deps.storage, &stake.denom, (bankroll - house_risk)?)?;
+
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender.clone(),
+            amount: vec![Coin {
+                denom: stake.denom.clone(),
+                amount: payout,
+            }],
+        }));
+
+        payout
+    } else {
+        let bankroll = bankroll
+            .u128()
+            .checked_add(stake.amount.u128())
+            .ok_or(ContractError::Overflow {})?;
+        save_bankroll(&mut deps.storage, &stake.denom, Uint128(bankroll))?;
+
+        Uint128::zero()
+    };
+
+    add_player_game(
+        &mut deps.storage,
+        &env.message.sender,
+        game_id,
+        GameKind::House,
+    )?;
+
+    let payout = Coin {
+        denom: stake.denom.clone(),
+        amount: payout,
+    };
+
+    HouseGame {
+        player: env.message.sender,
+        bet,
+        stake,
+        roll,
+        payout: payout.amount,
+        settled_at: env.block.height,
+    }
+    .save(&mut deps.storage, game_id)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: Event::HouseBet {
+            game_id,
+            dice: &[roll],
+            payout: &payout,
+        }
+        .log(),
+        data: Some(to_binary(&HandleAnswer::HouseBet {
+            game_id,
+            roll,
+            payout,
+        })?),
+    })
+}
+
+#[cfg(not(feature = "commit-reveal"))]
+fn place_bet<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: &Config,
+    bet: MarketBet,
+    secret: u128,
+) -> HandleResult {
+    // same as `play_house`, except the payout comes from the config's payout table
+    // and every market has its own cap on what a single bet may win
+
+    let markets = config
+        .markets
+        .as_ref()
+        .ok_or(ContractError::MarketsDisabled {})?;
+    let market = markets.payout(&bet)?;
+
+    let stake = assert_stake(&env, config)?;
+    assert_may_play(&deps.storage, config, &env.message.sender)?;
+
+    let payout = stake
+        .amount
+        .u128()
+        .checked_mul(market.multiplier_bps as u128)
+        .map(|amount| Uint128(amount / 10_000))
+        .ok_or(ContractError::Overflow {})?;
+
+    let house_risk = (payout - stake.amount)?;
+    let bankroll = load_bankroll(&deps.storage, &stake.denom)?;
+    let max_win = bankroll
+        .u128()
+        .checked_mul(market.max_win_bps as u128)
+        .map(|amount| Uint128(amount / 10_000))
+        .ok_or(ContractError::Overflow {})?;
+
+    if house_risk > max_win {
+        return Err(ContractError::ExceedsMaxWin {
+            potential_win: Coin {
+                denom: stake.denom.clone(),
+                amount: house_risk,
+            },
+            max_win: Coin {
+                denom: stake.denom,
+                amount: max_win,
+            },
+        }
+        .into());
+    }
+
+    let game_id = next_game_id(&mut deps.storage)?;
+
+    let prng_seed = load_prng_seed(&deps.storage)?;
+    let (random_seed, next_prng_seed) = roll_seed(
+        &prng_seed,
+        env.block.height,
+        &env.contract.address,
+        game_id,
+        &[secret],
+    );
+    save_prng_seed(&mut deps.storage, &next_prng_seed)?;
+
+    let mut rng = ChaChaRng::from_seed(random_seed);
+    let dice = dice::roll_many(&mut rng, markets.dice_count, markets.sides)?;
+
+    let mut messages = vec![];
+    let payout = if bet.wins(&dice) {
+        save_bankroll(&mut deps.storage, &stake.denom, (bankroll - house_risk)?)?;
+
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender.clone(),
+            amount: vec![Coin {
+                denom: stake.denom.clone(),
+                amount: payout,
+            }],
+        }));
+
+        payout
+    } else {
+        let bankroll = bankroll
+            .u128()
+            .checked_add(stake.amount.u128())
+            .ok_or(ContractError::Overflow {})?;
+        save_bankroll(&mut deps.storage, &stake.denom, Uint128(bankroll))?;
+
+        Uint128::zero()
+    };
+
+    add_player_game(
+        &mut deps.storage,
+        &env.message.sender,
+        game_id,
+        GameKind::Market,
+    )?;
+
+    let payout = Coin {
+        denom: stake.denom.clone(),
+        amount: payout,
+    };
+
+    MarketGame {
+        player: env.message.sender,
+        bet,
+        stake,
+        dice: dice.clone(),
+        multiplier_bps: market.multiplier_bps,
+        payout: payout.amount,
+        settled_at: env.block.height,
+    }
+    .save(&mut deps.storage, game_id)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: Event::MarketBet {
+            game_id,
+            dice: &dice,
+            payout: &payout,
+        }
+        .log(),
+        data: Some(to_binary(&HandleAnswer::MarketBet {
+            game_id,
+            dice_roll: dice,
+            payout,
+        })?),
+    })
+}
+
+// Rolls the dice among the `eligible` seats and pays the winner
+fn settle_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: &Config,
+    game_id: u64,
+    mut game: Game,
+    eligible: &[usize],
+) -> HandleResult {
+    // once all secrets are known, we can derive a shared secret that no one knows
+    // the contract's own entropy is mixed in so neither the players nor a single
+    // person playing several seats can choose the result
+    // then we can roll the dice and choose a winner according to the game's rule
+    // e.g. classic with two seats: dice roll 1-3: first player wins / dice roll 4-6: second player wins
+    //
+    // if only some seats are eligible (commit-reveal forfeits) the game's rule can't apply,
+    // so the winner is picked among them with the classic die instead, which then
+    // replaces the game's rule so results report the rule the dice were rolled with
+    //
+    // the winner then gets all stakes and the game is archived
+    //
+    // with a jackpot configured a share of the pot goes into it, and one more die
+    // decides whether the winner also takes the whole jackpot
+
+    let secrets = game
+        .seated()
+        .map(|(_, seat)| seat.secret)
+        .collect::<Vec<_>>();
+
+    // with commit-reveal nothing that can change while secrets are revealed goes into the roll
+    let random_seed = match game.sealed_entropy {
+        Some((prng_seed, height)) => {
+            roll_seed(&prng_seed, height, &env.contract.address, game_id, &secrets).0
+        }
+        None => {
+            let prng_seed = load_prng_seed(&deps.storage)?;
+            let (random_seed, next_prng_seed) = roll_seed(
+                &prng_seed,
+                env.block.height,
+                &env.contract.address,
+                game_id,
+                &secrets,
+            );
+            save_prng_seed(&mut deps.storage, &next_prng_seed)?;
+            random_seed
+        }
+    };
+
+    let mut rng = ChaChaRng::from_seed(random_seed);
+
+    let winning_seat = if eligible.len() == game.players.len() {
+        let (dice, seat) = game.rule.play(&mut rng, game.seats)?;
+        game.dice = dice;
+        seat
+    } else {
+        let rule = DiceRule::classic(eligible.len() as u8, FACES_PER_SEAT);
+        let (dice, seat) = rule.play(&mut rng, eligible.len() as u8)?;
+        game.rule = rule;
+        game.dice = dice;
+        eligible[seat]
+    };
+
+    game.winner = Some(game.seat(game_id, winning_seat)?.player.clone());
+
+    if let Some(jackpot) = &config.jackpot {
+        let mut pool = load_jackpot(&deps.storage, &game.stake.denom)?;
+        let share = game.pot()?.multiply_ratio(jackpot.share_bps, 10_000u128);
+        pool.amount = Uint128(
+            pool.amount
+                .u128()
+                .checked_add(share.u128())
+                .ok_or(ContractError::Overflow {})?,
+        );
+
+        if dice::roll(&mut rng, jackpot.odds)? == jackpot.odds {
+            game.jackpot = pool.amount;
+            pool.last_won_game = Some(game_id);
+            pool.amount = Uint128::zero();
+        }
+
+        save_jackpot(&mut deps.storage, &game.stake.denom, &pool)?;
+    }
+
+    if game.status != GameStatus::Forfeited {
+        game.status = GameStatus::Settled;
+    }
+    game.settled_at = env.block.height;
+
+    game.save(&mut deps.storage, game_id)?;
+    save_last_settled(&mut deps.storage, game_id)?;
+
+    let winner = game.seat(game_id, winning_seat)?.player.clone();
+    let payout = Coin {
+        denom: game.stake.denom.clone(),
+        amount: winner_payout(config, &game)?.0,
+    };
+
+    record_payouts(&mut deps.storage, config, game_id, &game)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: Event::Settle {
+            game_id,
+            dice: &game.dice,
+            winner: &winner,
+            payout: &payout,
+        }
+        .log(),
+        data: Some(to_binary(&HandleAnswer::Settled {
+            game_id,
+            winner,
+            dice_roll: game.dice,
+            payout,
+        })?),
+    })
+}
+
+fn forfeit_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: &Config,
+    game_id: u64,
+    mut game: Game,
+) -> HandleResult {
+    // the reveal deadline passed without all secrets
+    // players who didn't reveal forfeit their stake and the dice are rolled among
+    // those who did, if nobody revealed everyone is refunded
+
+    let eligible = game
+        .seated()
+        .filter(|(_, seat)| seat.commitment.is_none())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    if !eligible.is_empty() {
+        game.status = GameStatus::Forfeited;
+        return settle_game(deps, env, config, game_id, game, &eligible);
+    }
+
+    game.status = GameStatus::Expired;
+    game.settled_at = env.block.height;
+    game.save(&mut deps.storage, game_id)?;
+
+    let mut messages = vec![];
+    for (_, seat) in game.seated() {
+        messages.extend(pay_seat(
+            &mut deps.storage,
+            &env,
+            config,
+            seat,
+            game.stake.clone(),
+        )?);
+    }
+
+    let refunded = game.stake.clone();
+    let reward = Coin {
+        denom: game.stake.denom,
+        amount: Uint128::zero(),
+    };
+
+    Ok(HandleResponse {
+        messages,
+        log: Event::Expire { game_id }.log(),
+        data: Some(to_binary(&HandleAnswer::Expired {
+            game_id,
+            refunded,
+            reward,
+        })?),
+    })
+}
+
+// What the winner and the fee recipient get out of the pot
+//
+// The winner gets the pot minus the fee and jackpot share if there are any,
+// plus the jackpot if they won it
+fn winner_payout(config: &Config, game: &Game) -> StdResult<(Uint128, Uint128)> {
+    let pot = game.pot()?;
+
+    let fee_amount = match &config.fee {
+        Some(fee) => pot.multiply_ratio(fee.bps, 10_000u128),
+        None => Uint128::zero(),
+    };
+
+    let jackpot_share = match &config.jackpot {
+        Some(jackpot) => pot.multiply_ratio(jackpot.share_bps, 10_000u128),
+        None => Uint128::zero(),
+    };
+
+    let winnings = ((pot - fee_amount)? - jackpot_share)? + game.jackpot;
+
+    Ok((winnings, fee_amount))
+}
+
+// Leaves the pot to `game.winner` to claim, or credits it to their balance,
+// and leaves the fee to its recipient to claim
+//
+// Nothing is sent here, so a transfer that fails can't stop a game from settling
+fn record_payouts<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    game_id: u64,
+    game: &Game,
+) -> StdResult<()> {
+    let winner = game
+        .winner
+        .as_ref()
+        .and_then(|winner| game.seat_of(winner))
+        .ok_or(ContractError::NoWinner { game_id })?;
+
+    let (winnings, fee_amount) = winner_payout(config, game)?;
+
+    let seat = game.seat(game_id, winner)?;
+    let winnings = Coin {
+        denom: game.stake.denom.clone(),
+        amount: winnings,
+    };
+
+    if seat.from_balance {
+        credit(storage, &seat.player, &winnings)?;
+    } else {
+        add_unclaimed(storage, &seat.player, game_id, winnings)?;
+    }
+
+    if !fee_amount.is_zero() {
+        if let Some(fee) = &config.fee {
+            add_unclaimed(
+                storage,
+                &fee.recipient,
+                game_id,
+                Coin {
+                    denom: game.stake.denom.clone(),
+                    amount: fee_amount,
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////
+//////////////////////////////// Query ////////////////////////////////
+///////////////////////////////////////////////////////////////////////
+
+// These are getters, we only return what's public
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    // `game_id: None` returns the most recently settled game
+    GetResult {
+        game_id: Option<u64>,
+    },
+    GetGame {
+        game_id: u64,
+    },
+    GetHouseGame {
+        game_id: u64,
+    },
+    GetMarketBet {
+        game_id: u64,
+    },
+    Bankroll {},
+    Jackpot {},
+    // newest first, authenticated with a key from `CreateViewingKey` or `SetViewingKey`
+    MyGames {
+        address: HumanAddr,
+        key: String,
+        // default to the first page of DEFAULT_PAGE_SIZE games
+        page: Option<u32>,
+        page_size: Option<u32>,
+    },
+    // what `address` holds in the contract for each accepted denom, authenticated like `MyGames`
+    Balance {
+        address: HumanAddr,
+        key: String,
+    },
+    // authenticated with a signed permit instead of a viewing key
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
+    Config {},
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    // same as `QueryMsg::MyGames` for the permit's signer, needs the `history` permission
+    MyGames {
+        page: Option<u32>,
+        page_size: Option<u32>,
+    },
+    // same as `QueryMsg::Balance`, needs the `balance` permission
+    Balance {},
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 10;
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+// The per-id queries below and `Jackpot` are public and ids are sequential, so they never
+// return addresses or what anyone staked or won, players see that with `MyGames`
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct Result {
+    game_id: u64,
+    winning_seat: usize,
+    // the winner also took the jackpot
+    jackpot_won: bool,
+    // commit-reveal only: some players didn't reveal in time, so `rule` is the classic die
+    // among the players who did, in seat order
+    forfeited: bool,
+    rule: DiceRule,
+    // every die rolled, grouped by seat for `HighestTotal`
+    dice: Vec<u16>,
+    total: u32,
+    settled_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct GameInfo {
+    game_id: u64,
+    status: GameStatus,
+    seats: u8,
+    taken_seats: u8,
+    rule: DiceRule,
+    // what joining costs, only while the game is open
+    stake: Option<Coin>,
+    created_at_height: u64,
+    created_at_time: u64,
+    // first block at which an open game can be expired
+    expires_at_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct HouseResult {
+    game_id: u64,
+    bet: HouseBet,
+    roll: u16,
+    won: bool,
+    settled_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct MarketResult {
+    game_id: u64,
+    bet: MarketBet,
+    dice: Vec<u16>,
+    total: u32,
+    multiplier_bps: u32,
+    won: bool,
+    settled_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct JackpotInfo {
+    denom: String,
+    amount: Uint128,
+    last_won_game: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    // the game isn't settled yet
+    Pending {},
+    // got back more than they staked
+    Won { amount: Uint128 },
+    // got back less than they staked
+    Lost { amount: Uint128 },
+    // got their stake back
+    Even {},
+}
+
+impl Outcome {
+    fn new(stake: Uint128, payout: Uint128) -> StdResult<Outcome> {
+        Ok(if payout > stake {
+            Outcome::Won {
+                amount: (payout - stake)?,
+            }
+        } else if payout < stake {
+            Outcome::Lost {
+                amount: (stake - payout)?,
+            }
+        } else {
+            Outcome::Even {}
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct PlayerGame {
+    game_id: u64,
+    kind: GameKind,
+    stake: Coin,
+    // every die rolled, empty until the game is settled
+    dice: Vec<u16>,
+    outcome: Outcome,
+}
+
+// What `player` got out of a player-vs-player game
+fn player_game(
+    config: &Config,
+    game_id: u64,
+    game: Game,
+    player: &HumanAddr,
+) -> StdResult<PlayerGame> {
+    let outcome = match game.status {
+        GameStatus::Open | GameStatus::Revealing => Outcome::Pending {},
+        GameStatus::Settled | GameStatus::Forfeited => {
+            if game.winner.as_ref() == Some(player) {
+                Outcome::new(game.stake.amount, winner_payout(config, &game)?.0)?
+            } else {
+                Outcome::Lost {
+                    amount: game.stake.amount,
+                }
+            }
+        }
+        // a game that filled up only expires when nobody revealed, and then is fully refunded
+        GameStatus::Expired if game.is_full() => Outcome::Even {},
+        GameStatus::Expired => {
+            let reward = game
+                .stake
+                .amount
+                .multiply_ratio(config.crank_reward_bps, 10_000u128);
+            Outcome::new(game.stake.amount, (game.stake.amount - reward)?)?
+        }
+    };
+
+    Ok(PlayerGame {
+        game_id,
+        kind: GameKind::Dice,
+        stake: game.stake,
+        dice: game.dice,
+        outcome,
+    })
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
+    match msg {
+        QueryMsg::GetResult { game_id } => {
+            let game_id = match game_id {
+                Some(game_id) => game_id,
+                None => {
+                    load_last_settled(&deps.storage)?.ok_or(ContractError::NoGameFinished {})?
+                }
+            };
+
+            let game = Game::load(&deps.storage, game_id)?;
+
+            if game.status == GameStatus::Expired {
+                return Err(ContractError::ExpiredUnfilled { game_id }.into());
+            }
+
+            match game.winner.as_ref().and_then(|winner| game.seat_of(winner)) {
+                Some(winning_seat) => to_binary(&Result {
+                    game_id,
+                    winning_seat,
+                    jackpot_won: !game.jackpot.is_zero(),
+                    forfeited: game.status == GameStatus::Forfeited,
+                    total: dice::total(&game.dice),
+                    rule: game.rule,
+                    dice: game.dice,
+                    settled_at: game.settled_at,
+                }),
+                None => Err(ContractError::StillWaiting { game_id }.into()),
+            }
+        }
+        QueryMsg::GetGame { game_id } => {
+            let config = Config::load(&deps.storage)?;
+            let game = Game::load(&deps.storage, game_id)?;
+
+            to_binary(&GameInfo {
+                game_id,
+                status: game.status,
+                seats: game.seats,
+                taken_seats: game.taken_seats() as u8,
+                stake: if game.status == GameStatus::Open {
+                    Some(game.stake)
+                } else {
+                    None
+                },
+                rule: game.rule,
+                created_at_height: game.created_at_height,
+                created_at_time: game.created_at_time,
+                expires_at_height: game.created_at_height.saturating_add(config.expiry_blocks),
+            })
+        }
+        QueryMsg::GetHouseGame { game_id } => {
+            let game = HouseGame::load(&deps.storage, game_id)?;
+
+            to_binary(&HouseResult {
+                game_id,
+                won: !game.payout.is_zero(),
+                bet: game.bet,
+                roll: game.roll,
+                settled_at: game.settled_at,
+            })
+        }
+        QueryMsg::GetMarketBet { game_id } => {
+            let game = MarketGame::load(&deps.storage, game_id)?;
+
+            to_binary(&MarketResult {
+                game_id,
+                won: !game.payout.is_zero(),
+                bet: game.bet,
+                total: dice::total(&game.dice),
+                dice: game.dice,
+                multiplier_bps: game.multiplier_bps,
+                settled_at: game.settled_at,
+            })
+        }
+        QueryMsg::Bankroll {} => {
+            let config = Config::load(&deps.storage)?;
+
+            let bankroll = config
+                .stake_limits
+                .iter()
+                .map(|limit| {
+                    Ok(Coin {
+                        denom: limit.denom.clone(),
+                        amount: load_bankroll(&deps.storage, &limit.denom)?,
+                    })
+                })
+                .collect::<StdResult<Vec<Coin>>>()?;
+
+            to_binary(&bankroll)
+        }
+        QueryMsg::Jackpot {} => {
+            let config = Config::load(&deps.storage)?;
+
+            let jackpots = config
+                .stake_limits
+                .iter()
+                .map(|limit| {
+                    let pool = load_jackpot(&deps.storage, &limit.denom)?;
+
+                    Ok(JackpotInfo {
+                        denom: limit.denom.clone(),
+                        amount: pool.amount,
+                        last_won_game: pool.last_won_game,
+                    })
+                })
+                .collect::<StdResult<Vec<JackpotInfo>>>()?;
+
+            to_binary(&jackpots)
+        }
+        QueryMsg::MyGames {
+            address,
+            key,
+            page,
+            page_size,
+        } => {
+            check_viewing_key(&deps.storage, &address, &key)?;
+
+            query_my_games(deps, address, page, page_size)
+        }
+        QueryMsg::Balance { address, key } => {
+            check_viewing_key(&deps.storage, &address, &key)?;
+
+            query_balance(deps, address)
+        }
+        QueryMsg::WithPermit { permit, query } => {
+            let contract_address =
+                ReadonlySingleton::new(&deps.storage, b"contract_address").load()?;
+
+            match query {
+                QueryWithPermit::MyGames { page, page_size } => {
+                    let address = permit::validate(
+                        &deps.storage,
+                        &deps.api,
+                        &permit,
+                        &contract_address,
+                        Permission::History,
+                    )?;
+
+                    query_my_games(deps, address, page, page_size)
+                }
+                QueryWithPermit::Balance {} => {
+                    let address = permit::validate(
+                        &deps.storage,
+                        &deps.api,
+                        &permit,
+                        &contract_address,
+                        Permission::Balance,
+                    )?;
+
+                    query_balance(deps, address)
+                }
+            }
+        }
+        QueryMsg::Config {} => to_binary(&Config::load(&deps.storage)?),
+    }
+}
+
+fn query_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> QueryResult {
+    let config = Config::load(&deps.storage)?;
+
+    let balance = config
+        .stake_limits
+        .iter()
+        .map(|limit| {
+            Ok(Coin {
+                denom: limit.denom.clone(),
+                amount: load_balance(&deps.storage, &address, &limit.denom)?,
+            })
+        })
+        .collect::<StdResult<Vec<Coin>>>()?;
+
+    to_binary(&balance)
+}
+
+fn query_my_games<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> QueryResult {
+    let config = Config::load(&deps.storage)?;
+    let page = page.unwrap_or(0) as usize;
+    let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE) as usize;
+
+    let games = load_player_games(&deps.storage, &address)?
+        .into_iter()
+        .rev()
+        .skip(page.saturating_mul(page_size))
+        .take(page_size)
+        .map(|(game_id, kind)| match kind {
+            GameKind::Dice => {
+                let game = Game::load(&deps.storage, game_id)?;
+                player_game(&config, game_id, game, &address)
+            }
+            GameKind::House => {
+                let game = HouseGame::load(&deps.storage, game_id)?;
+
+                Ok(PlayerGame {
+                    game_id,
+                    kind,
+                    outcome: Outcome::new(game.stake.amount, game.payout)?,
+                    stake: game.stake,
+                    dice: vec![game.roll],
+                })
+            }
+            GameKind::Market => {
+                let game = MarketGame::load(&deps.storage, game_id)?;
+
+                Ok(PlayerGame {
+                    game_id,
+                    kind,
+                    outcome: Outcome::new(game.stake.amount, game.payout)?,
+                    stake: game.stake,
+                    dice: game.dice,
+                })
+            }
+        })
+        .collect::<StdResult<Vec<PlayerGame>>>()?;
+
+    to_binary(&games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, StdError};
+
+    const STAKE: u128 = 1_000_000;
+
+    fn init_msg() -> InitMsg {
+        InitMsg {
+            admin: None,
+            prng_seed: Binary(b"seed".to_vec()),
+            stake_limits: vec![StakeLimit {
+                denom: "uscrt".to_string(),
+                min: Uint128(1),
+                max: Uint128(STAKE * 1_000),
+            }],
+            fee: None,
+            expiry_blocks: None,
+            crank_reward_bps: None,
+            #[cfg(feature = "commit-reveal")]
+            reveal_blocks: None,
+            max_seats: None,
+            house: None,
+            markets: None,
+            jackpot: None,
+            tokens: None,
+            access_mode: None,
+            opponent_cooldown_blocks: None,
+        }
+    }
+
+    // `admin` instantiates the contract
+    fn instantiate(msg: InitMsg) -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("admin", &[]), msg).unwrap();
+        deps
+    }
+
+    // Everything `res` sends to `to`, in `denom`
+    fn sent(res: &HandleResponse, to: &str, denom: &str) -> u128 {
+        res.messages
+            .iter()
+            .filter_map(|msg| match msg {
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address, amount, ..
+                }) if to_address.0 == to => Some(amount),
+                _ => None,
+            })
+            .flatten()
+            .filter(|coin| coin.denom == denom)
+            .map(|coin| coin.amount.u128())
+            .sum()
+    }
+
+    fn unclaimed(deps: &Extern<MockStorage, MockApi, MockQuerier>, player: &str) -> u128 {
+        load_unclaimed(&deps.storage, &HumanAddr::from(player))
+            .unwrap()
+            .iter()
+            .map(|(_, coin)| coin.amount.u128())
+            .sum()
+    }
+
+    fn balance(deps: &Extern<MockStorage, MockApi, MockQuerier>, player: &str) -> u128 {
+        load_balance(&deps.storage, &HumanAddr::from(player), "uscrt")
+            .unwrap()
+            .u128()
+    }
+
+    // The messages below take `secret` as is, or commit to it with commit-reveal
+    #[cfg(not(feature = "commit-reveal"))]
+    fn create_game_msg(_player: &str, seats: u8, secret: u128, stake: Option<Coin>) -> HandleMsg {
+        HandleMsg::CreateGame {
+            seats: Some(seats),
+            rule: None,
+            secret,
+            stake,
+        }
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    fn create_game_msg(player: &str, seats: u8, secret: u128, stake: Option<Coin>) -> HandleMsg {
+        HandleMsg::CreateGame {
+            seats: Some(seats),
+            rule: None,
+            hash: commitment(secret, &HumanAddr::from(player)),
+            stake,
+        }
+    }
+
+    #[cfg(not(feature = "commit-reveal"))]
+    fn join_game_msg(_player: &str, game_id: u64, secret: u128, stake: Option<Coin>) -> HandleMsg {
+        HandleMsg::JoinGame {
+            game_id,
+            secret,
+            stake,
+        }
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    fn join_game_msg(player: &str, game_id: u64, secret: u128, stake: Option<Coin>) -> HandleMsg {
+        HandleMsg::JoinGame {
+            game_id,
+            hash: commitment(secret, &HumanAddr::from(player)),
+            stake,
+        }
+    }
+
+    // Seats `players` at a new game with the funds sent along until it settles, revealing
+    // every secret with commit-reveal, the players' secrets are their seat index
+    fn play_game(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, players: &[&str]) -> u64 {
+        let game_id = load_game_count(&deps.storage);
+        let stake = [coin(STAKE, "uscrt")];
+
+        let msg = create_game_msg(players[0], players.len() as u8, 0, None);
+        handle(deps, mock_env(players[0], &stake), msg).unwrap();
+        for (seat, player) in players.iter().enumerate().skip(1) {
+            let msg = join_game_msg(player, game_id, seat as u128, None);
+            handle(deps, mock_env(*player, &stake), msg).unwrap();
+        }
+
+        #[cfg(feature = "commit-reveal")]
+        for (seat, player) in players.iter().enumerate() {
+            let msg = HandleMsg::Reveal {
+                game_id,
+                secret: seat as u128,
+            };
+            handle(deps, mock_env(*player, &[]), msg).unwrap();
+        }
+
+        game_id
+    }
+
+    fn load_game_count(storage: &MockStorage) -> u64 {
+        ReadonlySingleton::new(storage, b"game_count")
+            .load()
+            .unwrap()
+    }
+
+    #[cfg(not(feature = "commit-reveal"))]
+    fn join_msg(_player: &str, secret: u128) -> HandleMsg {
+        HandleMsg::Join {
+            secret,
+            stake: None,
+        }
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    fn join_msg(player: &str, secret: u128) -> HandleMsg {
+        HandleMsg::Join {
+            hash: commitment(secret, &HumanAddr::from(player)),
+            stake: None,
+        }
+    }
+
+    // The game a `Join`, `CreateGame` or `JoinGame` response is about
+    fn answer_game_id(res: &HandleResponse) -> u64 {
+        match from_binary(res.data.as_ref().unwrap()).unwrap() {
+            HandleAnswer::Created { game_id }
+            | HandleAnswer::Joined { game_id, .. }
+            | HandleAnswer::Settled { game_id, .. } => game_id,
+            _ => panic!("expected a game"),
+        }
+    }
+
+    fn assert_code(err: StdError, code: u16) {
+        match err {
+            StdError::GenericErr { msg, .. } => {
+                assert!(msg.contains(&format!("\"code\":{}", code)), "{}", msg)
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn expire_refunds_every_seat_less_the_crank_reward() {
+        let mut deps = instantiate(InitMsg {
+            crank_reward_bps: Some(100),
+            ..init_msg()
+        });
+
+        let msg = create_game_msg("alice", 3, 1, None);
+        handle(&mut deps, mock_env("alice", &[coin(STAKE, "uscrt")]), msg).unwrap();
+        let msg = join_game_msg("bob", 0, 2, None);
+        handle(&mut deps, mock_env("bob", &[coin(STAKE, "uscrt")]), msg).unwrap();
+
+        let mut env = mock_env("crank", &[]);
+        env.block.height += DEFAULT_EXPIRY_BLOCKS - 1;
+        let err = handle(&mut deps, env.clone(), HandleMsg::ExpireGame { game_id: 0 });
+        assert_code(err.unwrap_err(), 307);
+
+        env.block.height += 1;
+        let res = handle(&mut deps, env.clone(), HandleMsg::ExpireGame { game_id: 0 }).unwrap();
+
+        let reward = STAKE * 100 / 10_000;
+        assert_eq!(sent(&res, "alice", "uscrt"), STAKE - reward);
+        assert_eq!(sent(&res, "bob", "uscrt"), STAKE - reward);
+        assert_eq!(sent(&res, "crank", "uscrt"), 2 * reward);
+        assert_eq!(res.messages.len(), 3);
+
+        let err = handle(&mut deps, env, HandleMsg::ExpireGame { game_id: 0 });
+        assert_code(err.unwrap_err(), 308);
+    }
+
+    #[test]
+    fn balance_stakes_are_debited_and_refunded_to_the_balance() {
+        let mut deps = instantiate(InitMsg {
+            crank_reward_bps: Some(100),
+            ..init_msg()
+        });
+
+        for player in &["alice", "bob"] {
+            let env = mock_env(*player, &[coin(STAKE, "uscrt")]);
+            handle(&mut deps, env, HandleMsg::Deposit {}).unwrap();
+        }
+
+        let msg = HandleMsg::Withdraw {
+            amount: coin(STAKE + 1, "uscrt"),
+        };
+        let err = handle(&mut deps, mock_env("alice", &[]), msg);
+        assert_code(err.unwrap_err(), 208);
+
+        let msg = create_game_msg("alice", 3, 1, Some(coin(STAKE, "uscrt")));
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(balance(&deps, "alice"), 0);
+
+        // funds sent along can't be mixed with a stake from the balance
+        let msg = join_game_msg("bob", 0, 2, Some(coin(STAKE, "uscrt")));
+        let err = handle(&mut deps, mock_env("bob", &[coin(STAKE, "uscrt")]), msg);
+        assert_code(err.unwrap_err(), 209);
+
+        let msg = join_game_msg("bob", 0, 2, Some(coin(STAKE, "uscrt")));
+        handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+        assert_eq!(balance(&deps, "bob"), 0);
+
+        let mut env = mock_env("crank", &[]);
+        env.block.height += DEFAULT_EXPIRY_BLOCKS;
+        let res = handle(&mut deps, env, HandleMsg::ExpireGame { game_id: 0 }).unwrap();
+
+        // only the crank reward leaves the contract, the refunds stay in the balances
+        let reward = STAKE * 100 / 10_000;
+        assert_eq!(sent(&res, "crank", "uscrt"), 2 * reward);
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(balance(&deps, "alice"), STAKE - reward);
+        assert_eq!(balance(&deps, "bob"), STAKE - reward);
+
+        let msg = HandleMsg::Withdraw {
+            amount: coin(STAKE - reward, "uscrt"),
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        assert_eq!(sent(&res, "alice", "uscrt"), STAKE - reward);
+        assert_eq!(balance(&deps, "alice"), 0);
+    }
+
+    #[test]
+    fn settle_leaves_the_whole_pot_to_claim() {
+        let fee = Fee {
+            bps: 200,
+            recipient: HumanAddr::from("fee"),
+        };
+        let jackpot = Jackpot {
+            share_bps: 100,
+            odds: 1_000,
+        };
+        let mut deps = instantiate(InitMsg {
+            fee: Some(fee),
+            jackpot: Some(jackpot),
+            ..init_msg()
+        });
+
+        let game_id = play_game(&mut deps, &["alice", "bob"]);
+
+        let game = Game::load(&deps.storage, game_id).unwrap();
+        assert!(game.status == GameStatus::Settled);
+
+        let winner = game.winner.unwrap();
+        let loser = if winner.0 == "alice" { "bob" } else { "alice" };
+        let pool = load_jackpot(&deps.storage, "uscrt").unwrap().amount.u128();
+        let fee = 2 * STAKE * 200 / 10_000;
+
+        // the jackpot share is back in the winnings if they hit it
+        assert_eq!(unclaimed(&deps, "fee"), fee);
+        assert_eq!(unclaimed(&deps, &winner.0) + fee + pool, 2 * STAKE);
+        assert_eq!(unclaimed(&deps, loser), 0);
+
+        let winnings = unclaimed(&deps, &winner.0);
+        let res = handle(
+            &mut deps,
+            mock_env(winner.clone(), &[]),
+            HandleMsg::ClaimAll {},
+        )
+        .unwrap();
+        assert_eq!(sent(&res, &winner.0, "uscrt"), winnings);
+        assert_eq!(unclaimed(&deps, &winner.0), 0);
+
+        let res = handle(
+            &mut deps,
+            mock_env("fee", &[]),
+            HandleMsg::Claim { game_id },
+        )
+        .unwrap();
+        assert_eq!(sent(&res, "fee", "uscrt"), fee);
+
+        let err = handle(
+            &mut deps,
+            mock_env("fee", &[]),
+            HandleMsg::Claim { game_id },
+        );
+        assert_code(err.unwrap_err(), 314);
+    }
+
+    #[test]
+    fn claim_all_pays_once_per_denom() {
+        let mut deps = instantiate(init_msg());
+
+        let claims = [
+            (1, coin(5, "uscrt")),
+            (2, coin(7, "uatom")),
+            (3, coin(11, "uscrt")),
+        ];
+        save_unclaimed(&mut deps.storage, &HumanAddr::from("alice"), &claims).unwrap();
+
+        let res = handle(&mut deps, mock_env("alice", &[]), HandleMsg::ClaimAll {}).unwrap();
+
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(sent(&res, "alice", "uscrt"), 16);
+        assert_eq!(sent(&res, "alice", "uatom"), 7);
+
+        let claims = res
+            .log
+            .iter()
+            .filter(|attr| attr.key == "action" && attr.value == "claim")
+            .count();
+        assert_eq!(claims, 3);
+
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Claimed { game_ids, payout } => {
+                assert_eq!(game_ids, vec![1, 2, 3]);
+                assert_eq!(payout, vec![coin(16, "uscrt"), coin(7, "uatom")]);
+            }
+            _ => panic!("expected a claim"),
+        }
+
+        assert_eq!(unclaimed(&deps, "alice"), 0);
+        let err = handle(&mut deps, mock_env("alice", &[]), HandleMsg::ClaimAll {});
+        assert_code(err.unwrap_err(), 314);
+    }
+
+    #[test]
+    fn leave_refunds_the_stake_and_frees_only_that_seat() {
+        let mut deps = instantiate(init_msg());
+        let stake = [coin(STAKE, "uscrt")];
+
+        let joined_seat = |res: HandleResponse| match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Joined { seat, .. } => seat,
+            _ => panic!("expected a join"),
+        };
+
+        let msg = create_game_msg("alice", 3, 0, None);
+        handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+        let msg = join_game_msg("bob", 0, 1, None);
+        handle(&mut deps, mock_env("bob", &stake), msg).unwrap();
+
+        let res = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::Leave { game_id: 0 },
+        )
+        .unwrap();
+        assert_eq!(sent(&res, "bob", "uscrt"), STAKE);
+        assert_eq!(res.messages.len(), 1);
+
+        let msg = join_game_msg("carol", 0, 2, None);
+        let res = handle(&mut deps, mock_env("carol", &stake), msg).unwrap();
+        assert_eq!(joined_seat(res), 1);
+
+        // alice's seat is freed, carol keeps hers
+        let res = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::Leave { game_id: 0 },
+        )
+        .unwrap();
+        assert_eq!(sent(&res, "alice", "uscrt"), STAKE);
+
+        let msg = join_game_msg("dave", 0, 3, None);
+        let res = handle(&mut deps, mock_env("dave", &stake), msg).unwrap();
+        assert_eq!(joined_seat(res), 0);
+        assert_eq!(
+            Game::load(&deps.storage, 0)
+                .unwrap()
+                .seat_of(&"carol".into()),
+            Some(1)
+        );
+
+        for player in &["carol", "dave"] {
+            let res = handle(
+                &mut deps,
+                mock_env(*player, &[]),
+                HandleMsg::Leave { game_id: 0 },
+            );
+            assert_eq!(sent(&res.unwrap(), player, "uscrt"), STAKE);
+        }
+
+        // the last player out discards the game
+        assert_code(Game::load(&deps.storage, 0).err().unwrap(), 300);
+    }
+
+    #[cfg(not(feature = "commit-reveal"))]
+    #[test]
+    fn house_bets_move_stakes_between_player_and_bankroll() {
+        let mut deps = instantiate(InitMsg {
+            house: Some(House {
+                edge_bps: 200,
+                max_win_bps: 1_000,
+                sides: 6,
+            }),
+            ..init_msg()
+        });
+
+        let msg = HandleMsg::PlayHouse {
+            bet: HouseBet::RollUnder { target: 4 },
+            secret: 0,
+        };
+        let err = handle(&mut deps, mock_env("alice", &[coin(STAKE, "uscrt")]), msg);
+        assert_code(err.unwrap_err(), 504);
+
+        let env = mock_env("admin", &[coin(100 * STAKE, "uscrt")]);
+        handle(&mut deps, env, HandleMsg::FundBankroll {}).unwrap();
+
+        // 3 of 6 faces pay 2x less the 2% edge
+        let payout = 2 * STAKE * 9_800 / 10_000;
+
+        for secret in 0..20 {
+            let bankroll = load_bankroll(&deps.storage, "uscrt").unwrap().u128();
+
+            let msg = HandleMsg::PlayHouse {
+                bet: HouseBet::RollUnder { target: 4 },
+                secret,
+            };
+            let env = mock_env("alice", &[coin(STAKE, "uscrt")]);
+            let res = handle(&mut deps, env, msg).unwrap();
+
+            let paid = sent(&res, "alice", "uscrt");
+            assert!(paid == 0 || paid == payout);
+            assert_eq!(
+                load_bankroll(&deps.storage, "uscrt").unwrap().u128() + paid,
+                bankroll + STAKE
+            );
+        }
+    }
+
+    #[cfg(not(feature = "commit-reveal"))]
+    #[test]
+    fn house_payout_overflow_is_an_error() {
+        let mut deps = instantiate(InitMsg {
+            stake_limits: vec![StakeLimit {
+                denom: "uscrt".to_string(),
+                min: Uint128(1),
+                max: Uint128(u128::MAX),
+            }],
+            house: Some(House {
+                edge_bps: 200,
+                max_win_bps: 10_000,
+                sides: 6,
+            }),
+            ..init_msg()
+        });
+
+        let msg = HandleMsg::PlayHouse {
+            bet: HouseBet::Exact { number: 6 },
+            secret: 0,
+        };
+        let env = mock_env("alice", &[coin(u128::MAX / 1_000, "uscrt")]);
+        assert_code(handle(&mut deps, env, msg).unwrap_err(), 207);
+    }
+
+    #[cfg(not(feature = "commit-reveal"))]
+    #[test]
+    fn market_bets_pay_the_listed_multiplier_from_the_bankroll() {
+        let mut deps = instantiate(init_msg());
+        let msg = HandleMsg::PlaceBet {
+            bet: MarketBet::Over { total: 7 },
+            secret: 0,
+        };
+        let err = handle(&mut deps, mock_env("alice", &[coin(STAKE, "uscrt")]), msg);
+        assert_code(err.unwrap_err(), 501);
+
+        let mut deps = instantiate(InitMsg {
+            markets: Some(Markets {
+                dice_count: 2,
+                sides: 6,
+                payouts: vec![Payout {
+                    bet: MarketBet::Over { total: 7 },
+                    multiplier_bps: 24_000,
+                    max_win_bps: 1_000,
+                }],
+            }),
+            ..init_msg()
+        });
+
+        let env = mock_env("admin", &[coin(100 * STAKE, "uscrt")]);
+        handle(&mut deps, env, HandleMsg::FundBankroll {}).unwrap();
+
+        let msg = HandleMsg::PlaceBet {
+            bet: MarketBet::Under { total: 7 },
+            secret: 0,
+        };
+        let err = handle(&mut deps, mock_env("alice", &[coin(STAKE, "uscrt")]), msg);
+        assert_code(err.unwrap_err(), 503);
+
+        for secret in 0..20 {
+            let bankroll = load_bankroll(&deps.storage, "uscrt").unwrap().u128();
+
+            let msg = HandleMsg::PlaceBet {
+                bet: MarketBet::Over { total: 7 },
+                secret,
+            };
+            let env = mock_env("alice", &[coin(STAKE, "uscrt")]);
+            let res = handle(&mut deps, env, msg).unwrap();
+
+            let paid = sent(&res, "alice", "uscrt");
+            assert!(paid == 0 || paid == STAKE * 24_000 / 10_000);
+            assert_eq!(
+                load_bankroll(&deps.storage, "uscrt").unwrap().u128() + paid,
+                bankroll + STAKE
+            );
+        }
+    }
+
+    #[test]
+    fn join_pairs_players_on_the_same_stake_only() {
+        let mut deps = instantiate(init_msg());
+
+        let join = |deps: &mut Extern<_, _, _>, player: &str, stake: Coin| {
+            let res = handle(deps, mock_env(player, &[stake]), join_msg(player, 0));
+            answer_game_id(&res.unwrap())
+        };
+
+        let micro = join(&mut deps, "alice", coin(1, "uscrt"));
+        let high = join(&mut deps, "bob", coin(STAKE, "uscrt"));
+        assert_ne!(micro, high);
+
+        assert_eq!(join(&mut deps, "carol", coin(STAKE, "uscrt")), high);
+        assert_eq!(join(&mut deps, "dave", coin(1, "uscrt")), micro);
+
+        // both rounds are full now, so the next player opens a fresh one
+        let fresh = join(&mut deps, "erin", coin(1, "uscrt"));
+        assert!(fresh != micro && fresh != high);
+    }
+
+    #[test]
+    fn access_list_applies_to_every_kind_of_game() {
+        let mut deps = instantiate(InitMsg {
+            access_mode: Some(AccessMode::Allowlist),
+            house: if cfg!(feature = "commit-reveal") {
+                None
+            } else {
+                Some(House {
+                    edge_bps: 200,
+                    max_win_bps: 1_000,
+                    sides: 6,
+                })
+            },
+            ..init_msg()
+        });
+        let stake = [coin(STAKE, "uscrt")];
+
+        let msg = create_game_msg("alice", 2, 0, None);
+        let err = handle(&mut deps, mock_env("alice", &stake), msg);
+        assert_code(err.unwrap_err(), 316);
+
+        #[cfg(not(feature = "commit-reveal"))]
+        {
+            let msg = HandleMsg::PlayHouse {
+                bet: HouseBet::RollUnder { target: 4 },
+                secret: 0,
+            };
+            let err = handle(&mut deps, mock_env("alice", &stake), msg);
+            assert_code(err.unwrap_err(), 316);
+        }
+
+        let msg = HandleMsg::UpdateAccessList {
+            add: vec![HumanAddr::from("alice")],
+            remove: vec![],
+        };
+        let err = handle(&mut deps, mock_env("alice", &[]), msg.clone());
+        assert_code(err.unwrap_err(), 102);
+        handle(&mut deps, mock_env("admin", &[]), msg).unwrap();
+
+        let msg = create_game_msg("alice", 2, 0, None);
+        handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+
+        let msg = join_game_msg("bob", 0, 1, None);
+        let err = handle(&mut deps, mock_env("bob", &stake), msg);
+        assert_code(err.unwrap_err(), 316);
+
+        // a denylist keeps out only the listed players
+        let msg = HandleMsg::SetAccessMode {
+            mode: AccessMode::Denylist,
+        };
+        handle(&mut deps, mock_env("admin", &[]), msg).unwrap();
+
+        let msg = create_game_msg("alice", 2, 0, None);
+        let err = handle(&mut deps, mock_env("alice", &stake), msg);
+        assert_code(err.unwrap_err(), 316);
+
+        let msg = join_game_msg("bob", 0, 1, None);
+        handle(&mut deps, mock_env("bob", &stake), msg).unwrap();
+    }
+
+    #[test]
+    fn join_skips_rounds_of_recent_opponents_without_dropping_them() {
+        let mut deps = instantiate(InitMsg {
+            opponent_cooldown_blocks: Some(100),
+            ..init_msg()
+        });
+        let stake = [coin(STAKE, "uscrt")];
+
+        play_game(&mut deps, &["alice", "bob"]);
+
+        let join = |deps: &mut Extern<_, _, _>, player: &str| {
+            let res = handle(deps, mock_env(player, &stake), join_msg(player, 0));
+            answer_game_id(&res.unwrap())
+        };
+
+        let alices = join(&mut deps, "alice");
+
+        let msg = join_game_msg("bob", alices, 1, None);
+        let err = handle(&mut deps, mock_env("bob", &stake), msg);
+        assert_code(err.unwrap_err(), 317);
+
+        // bob gets a round of their own, and alice's keeps waiting for the next player
+        let bobs = join(&mut deps, "bob");
+        assert_ne!(bobs, alices);
+        assert_eq!(join(&mut deps, "carol"), alices);
+        assert_eq!(join(&mut deps, "dave"), bobs);
+
+        // once the cooldown is over they're matched again
+        let alices = join(&mut deps, "alice");
+        let mut env = mock_env("bob", &stake);
+        env.block.height += 100;
+        let res = handle(&mut deps, env, join_msg("bob", 0)).unwrap();
+        assert_eq!(answer_game_id(&res), alices);
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn only_join_rounds_go_back_to_matchmaking_when_reopened() {
+        let mut deps = instantiate(init_msg());
+        let stake = [coin(STAKE, "uscrt")];
+
+        let join = |deps: &mut Extern<_, _, _>, player: &str| {
+            let res = handle(deps, mock_env(player, &stake), join_msg(player, 0));
+            answer_game_id(&res.unwrap())
+        };
+
+        let msg = create_game_msg("alice", 2, 0, None);
+        let created = answer_game_id(&handle(&mut deps, mock_env("alice", &stake), msg).unwrap());
+        let msg = join_game_msg("bob", created, 1, None);
+        handle(&mut deps, mock_env("bob", &stake), msg).unwrap();
+
+        let round = join(&mut deps, "carol");
+        assert_eq!(join(&mut deps, "dave"), round);
+
+        let mut env = mock_env("bob", &[]);
+        env.block.height += 50;
+        handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::Leave { game_id: created },
+        )
+        .unwrap();
+        env.message.sender = HumanAddr::from("dave");
+        handle(&mut deps, env, HandleMsg::Leave { game_id: round }).unwrap();
+
+        // the reopened round starts its expiry over
+        let game = Game::load(&deps.storage, round).unwrap();
+        assert!(game.status == GameStatus::Open);
+        assert_eq!(game.created_at_height, 12_345 + 50);
+
+        // the game alice created keeps waiting for a `JoinGame`
+        assert_eq!(join(&mut deps, "erin"), round);
+        assert_ne!(join(&mut deps, "frank"), created);
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn the_last_player_to_reveal_cant_leave() {
+        let mut deps = instantiate(init_msg());
+        let stake = [coin(STAKE, "uscrt")];
+
+        let msg = create_game_msg("alice", 3, 0, None);
+        handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+        for (seat, player) in ["bob", "carol"].iter().enumerate() {
+            let msg = join_game_msg(player, 0, seat as u128 + 1, None);
+            handle(&mut deps, mock_env(*player, &stake), msg).unwrap();
+        }
+
+        let reveal = |deps: &mut Extern<_, _, _>, player: &str, secret: u128| {
+            let msg = HandleMsg::Reveal { game_id: 0, secret };
+            handle(deps, mock_env(player, &[]), msg)
+        };
+        reveal(&mut deps, "alice", 0).unwrap();
+
+        // bob may still leave while carol hasn't revealed either
+        let res = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::Leave { game_id: 0 },
+        );
+        assert_eq!(sent(&res.unwrap(), "bob", "uscrt"), STAKE);
+
+        let msg = join_game_msg("dave", 0, 3, None);
+        handle(&mut deps, mock_env("dave", &stake), msg).unwrap();
+        reveal(&mut deps, "dave", 3).unwrap();
+
+        let err = handle(
+            &mut deps,
+            mock_env("carol", &[]),
+            HandleMsg::Leave { game_id: 0 },
+        );
+        assert_code(err.unwrap_err(), 405);
+
+        reveal(&mut deps, "carol", 2).unwrap();
+        assert!(Game::load(&deps.storage, 0).unwrap().status == GameStatus::Settled);
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn players_who_dont_reveal_forfeit_to_those_who_did() {
+        let mut deps = instantiate(init_msg());
+        let stake = [coin(STAKE, "uscrt")];
+
+        let reveal = |deps: &mut Extern<_, _, _>, player: &str, secret: u128| {
+            let msg = HandleMsg::Reveal { game_id: 0, secret };
+            handle(deps, mock_env(player, &[]), msg)
+        };
+
+        let msg = create_game_msg("alice", 3, 0, None);
+        handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+        assert_code(reveal(&mut deps, "alice", 0).unwrap_err(), 400);
+        for (seat, player) in ["bob", "carol"].iter().enumerate() {
+            let msg = join_game_msg(player, 0, seat as u128 + 1, None);
+            handle(&mut deps, mock_env(*player, &stake), msg).unwrap();
+        }
+
+        // the commitment binds the secret to the player who made it
+        assert_code(reveal(&mut deps, "alice", 1).unwrap_err(), 403);
+        assert_code(reveal(&mut deps, "bob", 0).unwrap_err(), 403);
+        assert_code(reveal(&mut deps, "dave", 0).unwrap_err(), 305);
+
+        let res = reveal(&mut deps, "alice", 0).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Revealed { game_id: 0, seat } => assert_eq!(seat, 0),
+            _ => panic!("expected a reveal"),
+        }
+        assert_code(reveal(&mut deps, "alice", 0).unwrap_err(), 402);
+        reveal(&mut deps, "carol", 2).unwrap();
+
+        let mut env = mock_env("crank", &[]);
+        env.block.height += DEFAULT_REVEAL_BLOCKS - 1;
+        let err = handle(&mut deps, env.clone(), HandleMsg::ExpireGame { game_id: 0 });
+        assert_code(err.unwrap_err(), 307);
+
+        env.block.height += 1;
+        let mut late = env.clone();
+        late.message.sender = HumanAddr::from("bob");
+        let msg = HandleMsg::Reveal {
+            game_id: 0,
+            secret: 1,
+        };
+        assert_code(handle(&mut deps, late, msg).unwrap_err(), 401);
+
+        handle(&mut deps, env, HandleMsg::ExpireGame { game_id: 0 }).unwrap();
+
+        // bob's stake goes to whichever of alice and carol wins
+        let game = Game::load(&deps.storage, 0).unwrap();
+        assert!(game.status == GameStatus::Forfeited);
+        let winner = game.winner.unwrap();
+        assert!(winner.0 == "alice" || winner.0 == "carol");
+        assert_eq!(unclaimed(&deps, &winner.0), 3 * STAKE);
+        assert_eq!(unclaimed(&deps, "bob"), 0);
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn nobody_revealing_refunds_every_seat_in_full() {
+        let mut deps = instantiate(InitMsg {
+            crank_reward_bps: Some(100),
+            ..init_msg()
+        });
+        let stake = [coin(STAKE, "uscrt")];
+
+        let msg = create_game_msg("alice", 2, 0, None);
+        handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+        let msg = join_game_msg("bob", 0, 1, None);
+        handle(&mut deps, mock_env("bob", &stake), msg).unwrap();
+
+        let mut env = mock_env("crank", &[]);
+        env.block.height += DEFAULT_REVEAL_BLOCKS;
+        let res = handle(&mut deps, env.clone(), HandleMsg::ExpireGame { game_id: 0 }).unwrap();
+
+        // nobody left a game to crank, so there's no reward
+        assert_eq!(sent(&res, "alice", "uscrt"), STAKE);
+        assert_eq!(sent(&res, "bob", "uscrt"), STAKE);
+        assert_eq!(res.messages.len(), 2);
+        assert!(Game::load(&deps.storage, 0).unwrap().status == GameStatus::Expired);
+
+        let err = handle(&mut deps, env, HandleMsg::ExpireGame { game_id: 0 });
+        assert_code(err.unwrap_err(), 308);
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn reveal_timing_and_other_games_dont_change_the_roll() {
+        let roll = |reveal_after: u64, settle_another_game: bool| {
+            let mut deps = instantiate(init_msg());
+            let stake = [coin(STAKE, "uscrt")];
+
+            let msg = create_game_msg("alice", 2, 7, None);
+            handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+            let msg = join_game_msg("bob", 0, 9, None);
+            handle(&mut deps, mock_env("bob", &stake), msg).unwrap();
+
+            if settle_another_game {
+                play_game(&mut deps, &["carol", "dave"]);
+            }
+
+            for (player, secret) in &[("alice", 7), ("bob", 9)] {
+                let mut env = mock_env(*player, &[]);
+                env.block.height += reveal_after;
+                let msg = HandleMsg::Reveal {
+                    game_id: 0,
+                    secret: *secret,
+                };
+                handle(&mut deps, env, msg).unwrap();
+            }
+
+            Game::load(&deps.storage, 0).unwrap().dice
+        };
+
+        let dice = roll(1, false);
+        assert!(!dice.is_empty());
+        for reveal_after in 2..40 {
+            assert_eq!(roll(reveal_after, false), dice);
+        }
+        assert_eq!(roll(1, true), dice);
+    }
+
+    #[test]
+    fn the_move_that_settles_a_game_is_logged_too() {
+        let mut deps = instantiate(init_msg());
+        let stake = [coin(STAKE, "uscrt")];
+
+        let msg = create_game_msg("alice", 2, 0, None);
+        handle(&mut deps, mock_env("alice", &stake), msg).unwrap();
+        let msg = join_game_msg("bob", 0, 1, None);
+        let joined = handle(&mut deps, mock_env("bob", &stake), msg).unwrap();
+
+        // under commit-reveal the game settles on the last reveal instead
+        #[cfg(not(feature = "commit-reveal"))]
+        let res = joined;
+        #[cfg(feature = "commit-reveal")]
+        let res = {
+            assert!(joined.log.iter().all(|attr| attr.value != "settle"));
+            let msg = HandleMsg::Reveal {
+                game_id: 0,
+                secret: 1,
+            };
+            handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+            let msg = HandleMsg::Reveal {
+                game_id: 0,
+                secret: 0,
+            };
+            handle(&mut deps, mock_env("alice", &[]), msg).unwrap()
+        };
+
+        let log = res
+            .log
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect::<Vec<_>>();
+        let (action, seat) = if cfg!(feature = "commit-reveal") {
+            ("reveal", "0")
+        } else {
+            ("join", "1")
+        };
+
+        assert_eq!(
+            &log[..3],
+            &[("action", action), ("game_id", "0"), ("seat", seat)]
+        );
+        assert!(log[3..].contains(&("action", "settle")));
+    }
 }
@@ -0,0 +1,370 @@
+use cosmwasm_std::StdResult;
+use rand::RngCore;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ContractError;
+
+pub const MAX_DICE: u8 = 10;
+pub const MAX_SIDES: u16 = 1000;
+
+// Number of distinct values `RngCore::next_u32` can return
+const RANGE: u64 = 1 << 32;
+
+// Largest multiple of `sides` that fits in the u32 range
+//
+// Values at or above it are rejected, so every face is backed by exactly `zone / sides` values
+fn zone(sides: u16) -> u64 {
+    RANGE - (RANGE % sides as u64)
+}
+
+// Rolls a fair die with `sides` faces, returning a number between 1 and `sides`
+//
+// Uses rejection sampling instead of `next_u32() % sides`, which favours the lower faces
+// whenever `sides` doesn't divide 2^32
+pub fn roll<R: RngCore>(rng: &mut R, sides: u16) -> StdResult<u16> {
+    if sides == 0 {
+        return Err(ContractError::DieWithoutSides {}.into());
+    }
+
+    let zone = zone(sides);
+
+    loop {
+        let value = rng.next_u32() as u64;
+        if value < zone {
+            return Ok((value % sides as u64) as u16 + 1);
+        }
+    }
+}
+
+// Rolls `count` fair dice with `sides` faces each
+pub fn roll_many<R: RngCore>(rng: &mut R, count: u8, sides: u16) -> StdResult<Vec<u16>> {
+    (0..count).map(|_| roll(rng, sides)).collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TotalRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WinCondition {
+    // one range of totals per seat, in seat order, together covering every possible total
+    Ranges { ranges: Vec<TotalRange> },
+    // two seats only: a total under `threshold` wins for the first seat, otherwise the second
+    SumThreshold { threshold: u32 },
+    // every seat rolls its own dice and the highest total wins,
+    // ties are broken by a fair roll among the tied seats
+    HighestTotal {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DiceRule {
+    pub dice_count: u8,
+    pub sides: u16,
+    pub win: WinCondition,
+}
+
+impl DiceRule {
+    // A single die where every seat wins on its own run of `faces_per_seat` faces
+    pub fn classic(seats: u8, faces_per_seat: u8) -> DiceRule {
+        let faces_per_seat = faces_per_seat as u32;
+
+        DiceRule {
+            dice_count: 1,
+            sides: (seats as u32 * faces_per_seat) as u16,
+            win: WinCondition::Ranges {
+                ranges: (0..seats as u32)
+                    .map(|seat| TotalRange {
+                        min: seat * faces_per_seat + 1,
+                        max: (seat + 1) * faces_per_seat,
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    fn min_total(&self) -> u32 {
+        self.dice_count as u32
+    }
+
+    fn max_total(&self) -> u32 {
+        self.dice_count as u32 * self.sides as u32
+    }
+
+    pub fn validate(&self, seats: u8) -> StdResult<()> {
+        if self.dice_count == 0 || self.dice_count > MAX_DICE {
+            return Err(ContractError::InvalidRule {
+                reason: format!("Must roll between 1 and {} dice.", MAX_DICE),
+            }
+            .into());
+        }
+
+        if self.sides < 2 || self.sides > MAX_SIDES {
+            return Err(ContractError::InvalidRule {
+                reason: format!("Dice must have between 2 and {} sides.", MAX_SIDES),
+            }
+            .into());
+        }
+
+        match &self.win {
+            WinCondition::Ranges { ranges } => {
+                if ranges.len() != seats as usize {
+                    return Err(ContractError::InvalidRule {
+                        reason: "Must have one range per seat.".to_string(),
+                    }
+                    .into());
+                }
+
+                let mut sorted = ranges.clone();
+                sorted.sort_by_key(|range| range.min);
+
+                let mut next = self.min_total();
+                for range in &sorted {
+                    // checking the upper end first keeps `range.max + 1` from overflowing
+                    if range.min != next || range.max < range.min || range.max > self.max_total() {
+                        return Err(ContractError::InvalidRule {
+                            reason: format!(
+                                "Ranges must cover every total from {} to {} exactly once.",
+                                self.min_total(),
+                                self.max_total()
+                            ),
+                        }
+                        .into());
+                    }
+                    next = range.max + 1;
+                }
+
+                if next != self.max_total() + 1 {
+                    return Err(ContractError::InvalidRule {
+                        reason: format!(
+                            "Ranges must cover every total from {} to {} exactly once.",
+                            self.min_total(),
+                            self.max_total()
+                        ),
+                    }
+                    .into());
+                }
+            }
+            WinCondition::SumThreshold { threshold } => {
+                if seats != 2 {
+                    return Err(ContractError::InvalidRule {
+                        reason: "A sum threshold only works with 2 seats.".to_string(),
+                    }
+                    .into());
+                }
+
+                if *threshold <= self.min_total() || *threshold > self.max_total() {
+                    return Err(ContractError::InvalidRule {
+                        reason: format!(
+                            "Threshold must be between {} and {}.",
+                            self.min_total() + 1,
+                            self.max_total()
+                        ),
+                    }
+                    .into());
+                }
+            }
+            WinCondition::HighestTotal {} => {}
+        }
+
+        Ok(())
+    }
+
+    // Rolls the dice for `seats` seats, returning every die rolled and the winning seat
+    //
+    // With `HighestTotal` the dice are grouped by seat, `dice_count` per seat in seat order
+    pub fn play<R: RngCore>(&self, rng: &mut R, seats: u8) -> StdResult<(Vec<u16>, usize)> {
+        match &self.win {
+            WinCondition::Ranges { ranges } => {
+                let dice = roll_many(rng, self.dice_count, self.sides)?;
+                let total = total(&dice);
+
+                let seat = ranges
+                    .iter()
+                    .position(|range| range.min <= total && total <= range.max)
+                    .ok_or(ContractError::NoWinningSeat { total })?;
+
+                Ok((dice, seat))
+            }
+            WinCondition::SumThreshold { threshold } => {
+                let dice = roll_many(rng, self.dice_count, self.sides)?;
+                let seat = if total(&dice) < *threshold { 0 } else { 1 };
+
+                Ok((dice, seat))
+            }
+            WinCondition::HighestTotal {} => {
+                let mut dice = vec![];
+                let mut totals = vec![];
+                for _ in 0..seats {
+                    let seat_dice = roll_many(rng, self.dice_count, self.sides)?;
+                    totals.push(total(&seat_dice));
+                    dice.extend(seat_dice);
+                }
+
+                let best = totals.iter().copied().max().unwrap_or(0);
+                let tied = (0..totals.len())
+                    .filter(|&seat| totals[seat] == best)
+                    .collect::<Vec<_>>();
+
+                let seat = match tied.len() {
+                    1 => tied[0],
+                    n => tied[roll(rng, n as u16)? as usize - 1],
+                };
+
+                Ok((dice, seat))
+            }
+        }
+    }
+}
+
+pub fn total(dice: &[u16]) -> u32 {
+    dice.iter().map(|&die| die as u32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::rand_core::impls;
+
+    // Replays a fixed list of values, so tests can hit the rejection boundary exactly
+    struct Replay(Vec<u32>);
+
+    impl RngCore for Replay {
+        fn next_u32(&mut self) -> u32 {
+            self.0.remove(0)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            impls::next_u64_via_u32(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn every_face_is_backed_by_the_same_number_of_values() {
+        for sides in 1..=u16::MAX {
+            let zone = zone(sides);
+            assert_eq!(zone % sides as u64, 0);
+            // the rejected tail is always shorter than one full cycle of faces
+            assert!(RANGE - zone < sides as u64);
+        }
+    }
+
+    #[test]
+    fn nothing_is_rejected_when_sides_divide_the_range() {
+        for &sides in &[1u16, 2, 4, 8, 16, 256, 32768] {
+            assert_eq!(zone(sides), RANGE);
+            let mut rng = Replay(vec![u32::MAX]);
+            assert_eq!(roll(&mut rng, sides).unwrap(), sides);
+        }
+    }
+
+    #[test]
+    fn last_value_below_the_zone_is_accepted() {
+        let zone = zone(6);
+        assert_eq!(zone, 4_294_967_292);
+
+        let mut rng = Replay(vec![(zone - 1) as u32]);
+        assert_eq!(roll(&mut rng, 6).unwrap(), 6);
+    }
+
+    #[test]
+    fn values_at_or_above_the_zone_are_rejected() {
+        let zone = zone(6);
+
+        // 2^32 % 6 == 4, so the top four values are all rejected before 0 is accepted
+        let mut rng = Replay(vec![
+            zone as u32,
+            (zone + 1) as u32,
+            (zone + 2) as u32,
+            u32::MAX,
+            0,
+        ]);
+        assert_eq!(roll(&mut rng, 6).unwrap(), 1);
+        assert!(rng.0.is_empty());
+    }
+
+    #[test]
+    fn zero_sides_is_an_error() {
+        let mut rng = Replay(vec![]);
+        assert!(roll(&mut rng, 0).is_err());
+    }
+
+    #[test]
+    fn classic_rule_is_valid_and_covers_every_face() {
+        for seats in 2..=6 {
+            let rule = DiceRule::classic(seats, 3);
+            rule.validate(seats).unwrap();
+            assert_eq!(rule.sides, seats as u16 * 3);
+        }
+    }
+
+    #[test]
+    fn ranges_with_gaps_or_overlaps_are_rejected() {
+        let rule = |ranges: Vec<(u32, u32)>| DiceRule {
+            dice_count: 2,
+            sides: 6,
+            win: WinCondition::Ranges {
+                ranges: ranges
+                    .into_iter()
+                    .map(|(min, max)| TotalRange { min, max })
+                    .collect(),
+            },
+        };
+
+        assert!(rule(vec![(2, 7), (8, 12)]).validate(2).is_ok());
+        assert!(rule(vec![(8, 12), (2, 7)]).validate(2).is_ok());
+        assert!(rule(vec![(2, 6), (8, 12)]).validate(2).is_err());
+        assert!(rule(vec![(2, 8), (8, 12)]).validate(2).is_err());
+        assert!(rule(vec![(2, 7), (8, 11)]).validate(2).is_err());
+        assert!(rule(vec![(2, 12)]).validate(2).is_err());
+        assert!(rule(vec![(2, 7), (8, u32::MAX)]).validate(2).is_err());
+    }
+
+    #[test]
+    fn sum_threshold_splits_on_the_total() {
+        let rule = DiceRule {
+            dice_count: 2,
+            sides: 6,
+            win: WinCondition::SumThreshold { threshold: 7 },
+        };
+        rule.validate(2).unwrap();
+        assert!(rule.validate(3).is_err());
+
+        // values 0 and 5 roll a 1 and a 6
+        let (dice, seat) = rule.play(&mut Replay(vec![0, 5]), 2).unwrap();
+        assert_eq!((dice, seat), (vec![1, 6], 1));
+
+        let (dice, seat) = rule.play(&mut Replay(vec![0, 4]), 2).unwrap();
+        assert_eq!((dice, seat), (vec![1, 5], 0));
+    }
+
+    #[test]
+    fn highest_total_wins_and_ties_are_rolled_off() {
+        let rule = DiceRule {
+            dice_count: 1,
+            sides: 6,
+            win: WinCondition::HighestTotal {},
+        };
+
+        let (dice, seat) = rule.play(&mut Replay(vec![1, 5, 3]), 3).unwrap();
+        assert_eq!((dice, seat), (vec![2, 6, 4], 1));
+
+        // seats 0 and 2 tie on 6, the roll-off picks the second of them
+        let (dice, seat) = rule.play(&mut Replay(vec![5, 1, 5, 1]), 3).unwrap();
+        assert_eq!((dice, seat), (vec![6, 2, 6], 2));
+    }
+}
@@ -0,0 +1,375 @@
+use cosmwasm_std::{to_vec, Coin, HumanAddr, StdError, Uint128};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Everything `handle` and `query` can fail with, besides storage and serialization errors
+//
+// Each error is returned as a `StdError::GenericErr` whose message is
+// `{"code":301,"error":{"game_full":{"game_id":7}},"message":"Game 7 is full."}`,
+// so clients can match on `code` and show their own text instead of `message`
+//
+// Codes are stable: never reuse or renumber them, only add new ones
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractError {
+    // 1xx: contract status, admin and config
+    Paused {},
+    NotAcceptingGames {},
+    Unauthorized {},
+    InvalidConfig {
+        reason: String,
+    },
+
+    // 2xx: deposits and stakes
+    WrongDeposit {
+        expected: Coin,
+        got: Coin,
+    },
+    NotOneCoin {
+        got: usize,
+    },
+    DenomNotAccepted {
+        denom: String,
+    },
+    StakeOutOfRange {
+        min: Uint128,
+        max: Uint128,
+        denom: String,
+    },
+    TokenOnlyThroughReceive {
+        denom: String,
+    },
+    TokenNotAccepted {
+        token: HumanAddr,
+    },
+    MissingReceiveMsg {},
+    Overflow {},
+    InsufficientBalance {
+        available: Coin,
+    },
+    FundsWithBalanceStake {},
+
+    // 3xx: player-vs-player games
+    GameNotFound {
+        game_id: u64,
+    },
+    GameFull {
+        game_id: u64,
+    },
+    GameExpired {
+        game_id: u64,
+    },
+    GameOver {
+        winner: HumanAddr,
+    },
+    GameStarted {
+        game_id: u64,
+    },
+    NotAPlayer {},
+    // no longer returned now that any seated player can leave, 306 stays reserved
+    OthersJoined {
+        game_id: u64,
+    },
+    NotExpiredYet {
+        game_id: u64,
+        expires_at: u64,
+    },
+    AlreadyRefunded {
+        game_id: u64,
+    },
+    InvalidSeats {
+        max: u8,
+    },
+    InvalidRule {
+        reason: String,
+    },
+    NoGameFinished {},
+    StillWaiting {
+        game_id: u64,
+    },
+    ExpiredUnfilled {
+        game_id: u64,
+    },
+    NothingToClaim {},
+    AlreadySeated {
+        game_id: u64,
+    },
+    NotAllowedToPlay {},
+    RecentOpponent {
+        opponent: HumanAddr,
+        until: u64,
+    },
+
+    // 4xx: commit-reveal
+    NotRevealing {
+        game_id: u64,
+    },
+    RevealDeadlinePassed {
+        game_id: u64,
+    },
+    AlreadyRevealed {},
+    CommitmentMismatch {},
+    InvalidCommitment {},
+    LastToReveal {
+        game_id: u64,
+    },
+
+    // 5xx: house and market bets
+    HouseDisabled {},
+    MarketsDisabled {},
+    InvalidBet {
+        reason: String,
+    },
+    NoMarket {},
+    ExceedsMaxWin {
+        potential_win: Coin,
+        max_win: Coin,
+    },
+    InsufficientBankroll {
+        available: Coin,
+    },
+    NoFunds {},
+
+    // 6xx: viewing keys and permits
+    WrongViewingKey {},
+    PermitNotForContract {
+        contract: HumanAddr,
+    },
+    PermitMissingPermission {
+        permission: String,
+    },
+    InvalidPermitSignature {},
+    PermitRevoked {
+        name: String,
+    },
+
+    // 9xx: broken invariants, these point at a bug in the contract rather than the message
+    DieWithoutSides {},
+    NoWinningSeat {
+        total: u32,
+    },
+    NoWinner {
+        game_id: u64,
+    },
+    EmptySeat {
+        game_id: u64,
+        seat: usize,
+    },
+}
+
+impl ContractError {
+    pub fn code(&self) -> u16 {
+        match self {
+            ContractError::Paused {} => 100,
+            ContractError::NotAcceptingGames {} => 101,
+            ContractError::Unauthorized {} => 102,
+            ContractError::InvalidConfig { .. } => 103,
+
+            ContractError::WrongDeposit { .. } => 200,
+            ContractError::NotOneCoin { .. } => 201,
+            ContractError::DenomNotAccepted { .. } => 202,
+            ContractError::StakeOutOfRange { .. } => 203,
+            ContractError::TokenOnlyThroughReceive { .. } => 204,
+            ContractError::TokenNotAccepted { .. } => 205,
+            ContractError::MissingReceiveMsg {} => 206,
+            ContractError::Overflow {} => 207,
+            ContractError::InsufficientBalance { .. } => 208,
+            ContractError::FundsWithBalanceStake {} => 209,
+
+            ContractError::GameNotFound { .. } => 300,
+            ContractError::GameFull { .. } => 301,
+            ContractError::GameExpired { .. } => 302,
+            ContractError::GameOver { .. } => 303,
+            ContractError::GameStarted { .. } => 304,
+            ContractError::NotAPlayer {} => 305,
+            ContractError::OthersJoined { .. } => 306,
+            ContractError::NotExpiredYet { .. } => 307,
+            ContractError::AlreadyRefunded { .. } => 308,
+            ContractError::InvalidSeats { .. } => 309,
+            ContractError::InvalidRule { .. } => 310,
+            ContractError::NoGameFinished {} => 311,
+            ContractError::StillWaiting { .. } => 312,
+            ContractError::ExpiredUnfilled { .. } => 313,
+            ContractError::NothingToClaim {} => 314,
+            ContractError::AlreadySeated { .. } => 315,
+            ContractError::NotAllowedToPlay {} => 316,
+            ContractError::RecentOpponent { .. } => 317,
+
+            ContractError::NotRevealing { .. } => 400,
+            ContractError::RevealDeadlinePassed { .. } => 401,
+            ContractError::AlreadyRevealed {} => 402,
+            ContractError::CommitmentMismatch {} => 403,
+            ContractError::InvalidCommitment {} => 404,
+            ContractError::LastToReveal { .. } => 405,
+
+            ContractError::HouseDisabled {} => 500,
+            ContractError::MarketsDisabled {} => 501,
+            ContractError::InvalidBet { .. } => 502,
+            ContractError::NoMarket {} => 503,
+            ContractError::ExceedsMaxWin { .. } => 504,
+            ContractError::InsufficientBankroll { .. } => 505,
+            ContractError::NoFunds {} => 506,
+
+            ContractError::WrongViewingKey {} => 600,
+            ContractError::PermitNotForContract { .. } => 601,
+            ContractError::PermitMissingPermission { .. } => 602,
+            ContractError::InvalidPermitSignature {} => 603,
+            ContractError::PermitRevoked { .. } => 604,
+
+            ContractError::DieWithoutSides {} => 900,
+            ContractError::NoWinningSeat { .. } => 901,
+            ContractError::NoWinner { .. } => 902,
+            ContractError::EmptySeat { .. } => 903,
+        }
+    }
+
+    // English text for people reading raw errors, clients should go by `code`
+    pub fn message(&self) -> String {
+        match self {
+            ContractError::Paused {} => "The contract is paused.".to_string(),
+            ContractError::NotAcceptingGames {} => {
+                "The contract is not accepting new games.".to_string()
+            }
+            ContractError::Unauthorized {} => "Unauthorized.".to_string(),
+            ContractError::InvalidConfig { reason } => reason.clone(),
+
+            ContractError::WrongDeposit { expected, .. } => format!(
+                "Must deposit {} {} to join this game.",
+                expected.amount, expected.denom
+            ),
+            ContractError::NotOneCoin { .. } => {
+                "Must deposit exactly one coin to enter the game.".to_string()
+            }
+            ContractError::DenomNotAccepted { denom } => {
+                format!("Stakes in {} are not accepted.", denom)
+            }
+            ContractError::StakeOutOfRange { min, max, denom } => {
+                format!("Stake must be between {} and {} {}.", min, max, denom)
+            }
+            ContractError::TokenOnlyThroughReceive { denom } => {
+                format!("{} can only be staked through its token contract.", denom)
+            }
+            ContractError::TokenNotAccepted { .. } => {
+                "Tokens from this contract are not accepted.".to_string()
+            }
+            ContractError::MissingReceiveMsg {} => "Must tell what the tokens are for.".to_string(),
+            ContractError::Overflow {} => "Amount is too large.".to_string(),
+            ContractError::InsufficientBalance { available } => format!(
+                "Balance only holds {} {}.",
+                available.amount, available.denom
+            ),
+            ContractError::FundsWithBalanceStake {} => {
+                "Don't send funds when staking from your balance.".to_string()
+            }
+
+            ContractError::GameNotFound { game_id } => format!("Game {} does not exist.", game_id),
+            ContractError::GameFull { game_id } => format!("Game {} is full.", game_id),
+            ContractError::GameExpired { game_id } => format!("Game {} has expired.", game_id),
+            ContractError::GameOver { winner } => {
+                format!("Game is already over. Winner is {}.", winner)
+            }
+            ContractError::GameStarted { .. } => "Game has already started.".to_string(),
+            ContractError::NotAPlayer {} => "You are not a player.".to_string(),
+            ContractError::OthersJoined { .. } => "Other players have already joined.".to_string(),
+            ContractError::NotExpiredYet {
+                game_id,
+                expires_at,
+            } => format!(
+                "Game {} can't be expired before block {}.",
+                game_id, expires_at
+            ),
+            ContractError::AlreadyRefunded { .. } => {
+                "Game has expired and was already refunded.".to_string()
+            }
+            ContractError::InvalidSeats { max } => {
+                format!("Games must have between 2 and {} seats.", max)
+            }
+            ContractError::InvalidRule { reason } => reason.clone(),
+            ContractError::NoGameFinished {} => "No game has finished yet.".to_string(),
+            ContractError::StillWaiting { .. } => "Still waiting for players.".to_string(),
+            ContractError::ExpiredUnfilled { .. } => {
+                "Game expired before all seats were taken.".to_string()
+            }
+            ContractError::NothingToClaim {} => "Nothing to claim.".to_string(),
+            ContractError::AlreadySeated { game_id } => {
+                format!("You already sit in game {}.", game_id)
+            }
+            ContractError::NotAllowedToPlay {} => "You are not allowed to play.".to_string(),
+            ContractError::RecentOpponent { opponent, until } => format!(
+                "You can't play against {} again before block {}.",
+                opponent, until
+            ),
+
+            ContractError::NotRevealing { .. } => "Game is not waiting for secrets.".to_string(),
+            ContractError::RevealDeadlinePassed { .. } => "Reveal deadline has passed.".to_string(),
+            ContractError::AlreadyRevealed {} => "You already revealed.".to_string(),
+            ContractError::CommitmentMismatch {} => {
+                "Secret doesn't match your commitment.".to_string()
+            }
+            ContractError::InvalidCommitment {} => {
+                "Commitment must be a 32 byte sha256 hash.".to_string()
+            }
+            ContractError::LastToReveal { .. } => {
+                "Everyone else revealed, reveal your secret instead of leaving.".to_string()
+            }
+
+            ContractError::HouseDisabled {} => "House games are disabled.".to_string(),
+            ContractError::MarketsDisabled {} => "Market bets are disabled.".to_string(),
+            ContractError::InvalidBet { reason } => reason.clone(),
+            ContractError::NoMarket {} => "No market for this bet.".to_string(),
+            ContractError::ExceedsMaxWin {
+                potential_win,
+                max_win,
+            } => format!(
+                "Bet could win {} {} but the house only covers {} {} per bet.",
+                potential_win.amount, potential_win.denom, max_win.amount, max_win.denom
+            ),
+            ContractError::InsufficientBankroll { available } => format!(
+                "Bankroll only holds {} {}.",
+                available.amount, available.denom
+            ),
+            ContractError::NoFunds {} => "Must send funds along.".to_string(),
+
+            ContractError::WrongViewingKey {} => {
+                "Wrong viewing key for this address or viewing key not set.".to_string()
+            }
+            ContractError::PermitNotForContract { contract } => {
+                format!("Permit doesn't apply to {}.", contract)
+            }
+            ContractError::PermitMissingPermission { permission } => {
+                format!("Permit doesn't allow {} queries.", permission)
+            }
+            ContractError::InvalidPermitSignature {} => "Permit signature is invalid.".to_string(),
+            ContractError::PermitRevoked { name } => format!("Permit {} was revoked.", name),
+
+            ContractError::DieWithoutSides {} => "A die must have at least one side.".to_string(),
+            ContractError::NoWinningSeat { total } => format!("No seat wins a total of {}.", total),
+            ContractError::NoWinner { game_id } => format!("Game {} has no winner.", game_id),
+            ContractError::EmptySeat { game_id, seat } => {
+                format!("Seat {} of game {} is empty.", seat, game_id)
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorMessage<'a> {
+    code: u16,
+    error: &'a ContractError,
+    message: String,
+}
+
+impl From<ContractError> for StdError {
+    fn from(error: ContractError) -> StdError {
+        let message = ErrorMessage {
+            code: error.code(),
+            error: &error,
+            message: error.message(),
+        };
+
+        match to_vec(&message).map(String::from_utf8) {
+            Ok(Ok(json)) => StdError::generic_err(json),
+            _ => StdError::generic_err(message.message),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use cosmwasm_std::{log, Coin, HumanAddr, LogAttribute};
+
+// What `handle` logs on every state transition of a game
+//
+// Indexers rely on these attributes, so only ever add to them:
+// - `action`: create, join, reveal, leave, settle, expire, claim, house_bet or market_bet
+// - `game_id`: the game the action applies to
+// - `seat`: the sender's seat, for create, join, reveal and leave
+// - `dice_result`: every die rolled, comma separated, for settle, house_bet and market_bet
+// - `winner`: the winning address, for settle
+// - `payout`: what was paid out, e.g. `150uscrt`, for settle, claim, house_bet and market_bet
+//
+// `ClaimAll` logs one claim per game, and the join or reveal that lets a game settle
+// is logged ahead of its settle
+//
+// Secrets and commitments are never logged
+pub enum Event<'a> {
+    Create {
+        game_id: u64,
+    },
+    Join {
+        game_id: u64,
+        seat: usize,
+    },
+    Reveal {
+        game_id: u64,
+        seat: usize,
+    },
+    Leave {
+        game_id: u64,
+        seat: usize,
+    },
+    Settle {
+        game_id: u64,
+        dice: &'a [u16],
+        winner: &'a HumanAddr,
+        payout: &'a Coin,
+    },
+    Expire {
+        game_id: u64,
+    },
+    Claim {
+        game_id: u64,
+        payout: &'a Coin,
+    },
+    HouseBet {
+        game_id: u64,
+        dice: &'a [u16],
+        payout: &'a Coin,
+    },
+    MarketBet {
+        game_id: u64,
+        dice: &'a [u16],
+        payout: &'a Coin,
+    },
+}
+
+fn dice_result(dice: &[u16]) -> String {
+    dice.iter()
+        .map(|die| die.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn payout(coin: &Coin) -> String {
+    format!("{}{}", coin.amount, coin.denom)
+}
+
+impl<'a> Event<'a> {
+    pub fn log(&self) -> Vec<LogAttribute> {
+        match self {
+            Event::Create { game_id } => vec![
+                log("action", "create"),
+                log("game_id", game_id),
+                log("seat", 0),
+            ],
+            Event::Join { game_id, seat } => vec![
+                log("action", "join"),
+                log("game_id", game_id),
+                log("seat", seat),
+            ],
+            Event::Reveal { game_id, seat } => vec![
+                log("action", "reveal"),
+                log("game_id", game_id),
+                log("seat", seat),
+            ],
+            Event::Leave { game_id, seat } => vec![
+                log("action", "leave"),
+                log("game_id", game_id),
+                log("seat", seat),
+            ],
+            Event::Settle {
+                game_id,
+                dice,
+                winner,
+                payout: coin,
+            } => vec![
+                log("action", "settle"),
+                log("game_id", game_id),
+                log("dice_result", dice_result(dice)),
+                log("winner", winner),
+                log("payout", payout(coin)),
+            ],
+            Event::Expire { game_id } => vec![log("action", "expire"), log("game_id", game_id)],
+            Event::Claim {
+                game_id,
+                payout: coin,
+            } => vec![
+                log("action", "claim"),
+                log("game_id", game_id),
+                log("payout", payout(coin)),
+            ],
+            Event::HouseBet {
+                game_id,
+                dice,
+                payout: coin,
+            } => vec![
+                log("action", "house_bet"),
+                log("game_id", game_id),
+                log("dice_result", dice_result(dice)),
+                log("payout", payout(coin)),
+            ],
+            Event::MarketBet {
+                game_id,
+                dice,
+                payout: coin,
+            } => vec![
+                log("action", "market_bet"),
+                log("game_id", game_id),
+                log("dice_result", dice_result(dice)),
+                log("payout", payout(coin)),
+            ],
+        }
+    }
+}
@@ -1,4 +1,9 @@
 pub mod contract;
+pub mod dice;
+pub mod error;
+pub mod events;
+pub mod permit;
+pub mod snip20;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm {
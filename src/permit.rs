@@ -0,0 +1,251 @@
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, HumanAddr, ReadonlyStorage, StdResult, Storage,
+};
+use cosmwasm_storage::{Bucket, ReadonlyBucket};
+use ripemd160::Ripemd160;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+
+// A SNIP-24 query permit: a wallet signs these params offline instead of sending
+// a transaction to set a viewing key
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    // `QueryWithPermit::MyGames`
+    History,
+    // `QueryWithPermit::Balance`
+    Balance,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitParams {
+    // contracts the permit is valid for, must include this one
+    pub allowed_tokens: Vec<HumanAddr>,
+    // lets the signer revoke the permit with `HandleMsg::RevokePermit`
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PubKey {
+    // always "tendermint/PubKeySecp256k1"
+    pub r#type: String,
+    // compressed secp256k1 public key
+    pub value: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+// The amino sign doc wallets sign for a permit, fields in alphabetical order
+// so it serializes exactly as the wallet signed it
+
+#[derive(Serialize)]
+struct SignDoc<'a> {
+    account_number: &'static str,
+    chain_id: &'a str,
+    fee: Fee,
+    memo: &'static str,
+    msgs: [SignedMsg<'a>; 1],
+    sequence: &'static str,
+}
+
+#[derive(Serialize)]
+struct Fee {
+    amount: [FeeCoin; 1],
+    gas: &'static str,
+}
+
+#[derive(Serialize)]
+struct FeeCoin {
+    amount: &'static str,
+    denom: &'static str,
+}
+
+#[derive(Serialize)]
+struct SignedMsg<'a> {
+    r#type: &'static str,
+    value: SignedParams<'a>,
+}
+
+#[derive(Serialize)]
+struct SignedParams<'a> {
+    allowed_tokens: &'a [HumanAddr],
+    permissions: &'a [Permission],
+    permit_name: &'a str,
+}
+
+// Returns the address that signed `permit` if it's valid for this contract and `permission`
+pub fn validate<S: ReadonlyStorage, A: Api>(
+    storage: &S,
+    api: &A,
+    permit: &Permit,
+    contract_address: &HumanAddr,
+    permission: Permission,
+) -> StdResult<HumanAddr> {
+    let params = &permit.params;
+
+    if !params.allowed_tokens.contains(contract_address) {
+        return Err(ContractError::PermitNotForContract {
+            contract: contract_address.clone(),
+        }
+        .into());
+    }
+
+    if !params.permissions.contains(&permission) {
+        return Err(ContractError::PermitMissingPermission {
+            permission: format!("{:?}", permission).to_lowercase(),
+        }
+        .into());
+    }
+
+    let account = api.human_address(&signer(api, permit)?)?;
+
+    if is_revoked(storage, &account, &params.permit_name)? {
+        return Err(ContractError::PermitRevoked {
+            name: params.permit_name.clone(),
+        }
+        .into());
+    }
+
+    Ok(account)
+}
+
+// The canonical address whose key signed `permit`
+fn signer<A: Api>(api: &A, permit: &Permit) -> StdResult<CanonicalAddr> {
+    let params = &permit.params;
+
+    let sign_doc = SignDoc {
+        account_number: "0",
+        chain_id: &params.chain_id,
+        fee: Fee {
+            amount: [FeeCoin {
+                amount: "0",
+                denom: "uscrt",
+            }],
+            gas: "1",
+        },
+        memo: "",
+        msgs: [SignedMsg {
+            r#type: "query_permit",
+            value: SignedParams {
+                allowed_tokens: &params.allowed_tokens,
+                permissions: &params.permissions,
+                permit_name: &params.permit_name,
+            },
+        }],
+        sequence: "0",
+    };
+    let message_hash = Sha256::digest(to_binary(&sign_doc)?.as_slice());
+
+    let pub_key = permit.signature.pub_key.value.as_slice();
+    let verified = api
+        .secp256k1_verify(
+            &message_hash,
+            permit.signature.signature.as_slice(),
+            pub_key,
+        )
+        .map_err(|_| ContractError::InvalidPermitSignature {})?;
+
+    if !verified {
+        return Err(ContractError::InvalidPermitSignature {}.into());
+    }
+
+    // cosmos addresses are ripemd160(sha256(public key))
+    let canonical = Ripemd160::digest(&Sha256::digest(pub_key));
+    Ok(CanonicalAddr(Binary(canonical.to_vec())))
+}
+
+pub fn revoke<S: Storage>(storage: &mut S, account: &HumanAddr, name: &str) -> StdResult<()> {
+    Bucket::multilevel(&[b"revoked_permits", account.0.as_bytes()], storage)
+        .save(name.as_bytes(), &true)
+}
+
+fn is_revoked<S: ReadonlyStorage>(storage: &S, account: &HumanAddr, name: &str) -> StdResult<bool> {
+    let revoked: Option<bool> =
+        ReadonlyBucket::multilevel(&[b"revoked_permits", account.0.as_bytes()], storage)
+            .may_load(name.as_bytes())?;
+    Ok(revoked.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockApi;
+    use cosmwasm_std::StdError;
+
+    // a permit a wallet signed on pulsar-2 for secret1399pyvvk3hvwgxwt3udkslsc5jl3rqv4yshfrl
+    fn wallet_permit() -> Permit {
+        let token = HumanAddr::from("secret1rf03820fp8gngzg2w02vd30ns78qkc8rg8dxaq");
+
+        Permit {
+            params: PermitParams {
+                allowed_tokens: vec![token],
+                permit_name: "memo_secret1rf03820fp8gngzg2w02vd30ns78qkc8rg8dxaq".to_string(),
+                chain_id: "pulsar-2".to_string(),
+                permissions: vec![Permission::History],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("A5M49l32ZrV+SDsPnoRv8fH7ivNC4gEX9prvd4RwvRaL")
+                        .unwrap(),
+                },
+                signature: Binary::from_base64(
+                    "hw/Mo3ZZYu1pEiDdymElFkuCuJzg9soDHw+4DxK7cL9rafiyykh7VynS+guotRAKXhfYMwCiyWmiznc6R+UlsQ==",
+                )
+                .unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn wallet_signed_permit_is_accepted() {
+        // the bech32 data of the signer's address
+        let expected = [
+            0x89, 0x4a, 0x12, 0x31, 0x96, 0x8d, 0xd8, 0xe4, 0x19, 0xcb, 0x8f, 0x1b, 0x68, 0x7e,
+            0x18, 0xa4, 0xbf, 0x11, 0x81, 0x95,
+        ];
+
+        assert_eq!(
+            signer(&MockApi::new(20), &wallet_permit()).unwrap(),
+            CanonicalAddr(Binary(expected.to_vec()))
+        );
+    }
+
+    fn assert_invalid_signature(permit: &Permit) {
+        match signer(&MockApi::new(20), permit) {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.contains("\"code\":603")),
+            _ => panic!("permit should have been rejected"),
+        }
+    }
+
+    #[test]
+    fn tampered_permit_is_rejected() {
+        let mut permit = wallet_permit();
+        permit.params.permissions.push(Permission::Balance);
+        assert_invalid_signature(&permit);
+
+        let mut permit = wallet_permit();
+        permit.params.chain_id = "secret-4".to_string();
+        assert_invalid_signature(&permit);
+    }
+}
@@ -0,0 +1,51 @@
+use cosmwasm_std::{to_binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// A SNIP-20 token contract that stakes can be placed in
+//
+// Its stakes use the contract address as their denom, e.g. in `StakeLimit`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Token {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+// The few SNIP-20 messages this contract sends
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Snip20Msg {
+    RegisterReceive {
+        code_hash: String,
+    },
+    SetViewingKey {
+        key: String,
+    },
+    Transfer {
+        recipient: HumanAddr,
+        amount: Uint128,
+    },
+}
+
+fn execute(token: &Token, msg: &Snip20Msg) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.address.clone(),
+        callback_code_hash: token.code_hash.clone(),
+        msg: to_binary(msg)?,
+        send: vec![],
+    }))
+}
+
+// Asks `token` to call this contract's `HandleMsg::Receive` whenever it is sent tokens
+pub fn register_receive_msg(token: &Token, code_hash: String) -> StdResult<CosmosMsg> {
+    execute(token, &Snip20Msg::RegisterReceive { code_hash })
+}
+
+pub fn set_viewing_key_msg(token: &Token, key: String) -> StdResult<CosmosMsg> {
+    execute(token, &Snip20Msg::SetViewingKey { key })
+}
+
+pub fn transfer_msg(token: &Token, recipient: HumanAddr, amount: Uint128) -> StdResult<CosmosMsg> {
+    execute(token, &Snip20Msg::Transfer { recipient, amount })
+}